@@ -1,7 +1,11 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::core::task_manager::{Task, TaskManager};
-use tauri::State;
+use crate::core::task_manager::{
+    current_timestamp, Comment, GraphData, Recurrence, RootSort, SubtaskSort, Task, TaskContext,
+    TaskDump, TaskFilter, TaskManager, TaskStats,
+};
+use tauri::{AppHandle, Emitter, State};
 
 #[tauri::command]
 pub async fn add_task(
@@ -9,7 +13,7 @@ pub async fn add_task(
     ordered: bool,
     task_manager: State<'_, Arc<TaskManager>>,
 ) -> Result<usize, String> {
-    Ok(task_manager.add_task(text, ordered))
+    task_manager.add_task(text, ordered)
 }
 
 #[tauri::command]
@@ -24,24 +28,31 @@ pub async fn add_subtask(
 #[tauri::command]
 pub async fn complete_task(
     id: usize,
+    app_handle: AppHandle,
     task_manager: State<'_, Arc<TaskManager>>,
 ) -> Result<(), String> {
-    task_manager.complete_task(id)
+    for unblocked_id in task_manager.complete_task_unblocking(id)? {
+        if let Err(e) = app_handle.emit("task-unblocked", unblocked_id) {
+            println!("Failed to emit task-unblocked event: {}", e);
+        }
+    }
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn uncomplete_task(
     id: usize,
+    reopen_ancestors: Option<bool>,
     task_manager: State<'_, Arc<TaskManager>>,
 ) -> Result<(), String> {
-    task_manager.uncomplete_task(id)
+    task_manager.uncomplete_task(id, reopen_ancestors.unwrap_or(false))
 }
 
 #[tauri::command]
 pub async fn toggle_ordered(
     id: usize,
     task_manager: State<'_, Arc<TaskManager>>,
-) -> Result<(), String> {
+) -> Result<usize, String> {
     task_manager.toggle_ordered(id)
 }
 
@@ -84,7 +95,7 @@ pub async fn reorder_subtasks(
     parent_id: usize,
     new_order: Vec<usize>,
     task_manager: State<'_, Arc<TaskManager>>,
-) -> Result<(), String> {
+) -> Result<bool, String> {
     task_manager.reorder_subtasks(parent_id, new_order)
 }
 
@@ -104,3 +115,827 @@ pub async fn update_task(
 ) -> Result<(), String> {
     task_manager.update_task_text(id, text)
 }
+
+#[tauri::command]
+pub async fn get_dependencies(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    task_manager.get_dependencies(id)
+}
+
+#[tauri::command]
+pub async fn get_dependents(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    task_manager.get_dependents(id)
+}
+
+#[tauri::command]
+pub async fn set_max_tasks(
+    max_tasks: Option<usize>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.set_max_tasks(max_tasks);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn save_as_template(
+    task_id: usize,
+    name: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.save_as_template(task_id, name)
+}
+
+#[tauri::command]
+pub async fn instantiate_template(
+    name: String,
+    parent: Option<usize>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<usize, String> {
+    task_manager.instantiate_template(&name, parent)
+}
+
+#[tauri::command]
+pub async fn get_subtask_labels(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<(usize, String, bool)>, String> {
+    task_manager.get_subtask_labels(id)
+}
+
+#[tauri::command]
+pub async fn begin_focus(task_manager: State<'_, Arc<TaskManager>>) -> Result<Vec<Task>, String> {
+    Ok(task_manager.begin_focus())
+}
+
+#[tauri::command]
+pub async fn focus_set(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Option<Vec<Task>>, String> {
+    Ok(task_manager.focus_set())
+}
+
+#[tauri::command]
+pub async fn end_focus(task_manager: State<'_, Arc<TaskManager>>) -> Result<(), String> {
+    task_manager.end_focus();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn import_outline(
+    text: String,
+    parent: Option<usize>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<usize>, String> {
+    task_manager.import_outline(&text, parent)
+}
+
+#[tauri::command]
+pub async fn promote_to_root(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.promote_to_root(id)
+}
+
+#[tauri::command]
+pub async fn get_active_tasks_for_root(
+    root_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    task_manager.get_active_tasks_for_root(root_id)
+}
+
+#[tauri::command]
+pub async fn set_waiting(
+    id: usize,
+    note: Option<String>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.set_waiting(id, note)
+}
+
+#[tauri::command]
+pub async fn get_waiting_tasks(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.get_waiting_tasks())
+}
+
+#[tauri::command]
+pub async fn get_recently_completed(
+    limit: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.get_recently_completed(limit))
+}
+
+#[tauri::command]
+pub async fn apply_reorders(
+    reorders: Vec<(usize, Vec<usize>)>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.apply_reorders(reorders)
+}
+
+#[tauri::command]
+pub async fn get_tasks(
+    ids: Vec<usize>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Option<Task>>, String> {
+    Ok(task_manager.get_tasks(ids))
+}
+
+#[tauri::command]
+pub async fn export_active_plaintext(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<String, String> {
+    Ok(task_manager.export_active_plaintext())
+}
+
+#[tauri::command]
+pub async fn set_priority(
+    id: usize,
+    priority: u8,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.set_priority(id, priority)
+}
+
+#[tauri::command]
+pub async fn set_due_date(
+    id: usize,
+    due_date: Option<i64>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.set_due_date(id, due_date)
+}
+
+#[tauri::command]
+pub async fn add_tag(
+    id: usize,
+    tag: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.add_tag(id, tag)
+}
+
+#[tauri::command]
+pub async fn remove_tag(
+    id: usize,
+    tag: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.remove_tag(id, &tag)
+}
+
+#[tauri::command]
+pub async fn filter_tasks(
+    filter: TaskFilter,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.filter_tasks(filter))
+}
+
+#[tauri::command]
+pub async fn rename_tag(
+    old: String,
+    new: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<usize, String> {
+    Ok(task_manager.rename_tag(&old, &new))
+}
+
+#[tauri::command]
+pub async fn get_active_tasks_page(
+    offset: usize,
+    limit: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(Vec<Task>, usize), String> {
+    Ok(task_manager.get_active_tasks_page(offset, limit))
+}
+
+#[tauri::command]
+pub async fn export_graph(task_manager: State<'_, Arc<TaskManager>>) -> Result<GraphData, String> {
+    Ok(task_manager.export_graph())
+}
+
+#[tauri::command]
+pub async fn export_subtree_json(
+    root_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<String, String> {
+    task_manager.export_subtree_json(root_id)
+}
+
+#[tauri::command]
+pub async fn get_ancestor_ids(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<usize>, String> {
+    task_manager.get_ancestor_ids(id)
+}
+
+#[tauri::command]
+pub async fn reorder_move(
+    parent_id: Option<usize>,
+    task_id: usize,
+    to_index: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.reorder_move(parent_id, task_id, to_index)
+}
+
+#[tauri::command]
+pub async fn add_attachment(
+    id: usize,
+    path: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.add_attachment(id, path)
+}
+
+#[tauri::command]
+pub async fn remove_attachment(
+    id: usize,
+    path: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.remove_attachment(id, &path)
+}
+
+#[tauri::command]
+pub async fn would_complete_project(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Option<usize>, String> {
+    task_manager.would_complete_project(id)
+}
+
+#[tauri::command]
+pub async fn quick_add(
+    input: String,
+    parent: Option<usize>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<usize, String> {
+    task_manager.quick_add(&input, parent)
+}
+
+#[tauri::command]
+pub async fn set_max_text_length(
+    max_length: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.set_max_text_length(max_length);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_agenda(
+    from: i64,
+    to: i64,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<(i64, Vec<Task>)>, String> {
+    Ok(task_manager.get_agenda(from, to))
+}
+
+#[tauri::command]
+pub async fn get_siblings(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    task_manager.get_siblings(id)
+}
+
+#[tauri::command]
+pub async fn start_task(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.start_task(id)
+}
+
+#[tauri::command]
+pub async fn stop_task(id: usize, task_manager: State<'_, Arc<TaskManager>>) -> Result<(), String> {
+    task_manager.stop_task(id)
+}
+
+#[tauri::command]
+pub async fn get_in_progress_tasks(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.get_in_progress_tasks())
+}
+
+#[tauri::command]
+pub async fn get_stats(task_manager: State<'_, Arc<TaskManager>>) -> Result<TaskStats, String> {
+    Ok(task_manager.get_stats())
+}
+
+#[tauri::command]
+pub async fn upsert_task(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.upsert_task(id)
+}
+
+#[tauri::command]
+pub async fn get_orphans(task_manager: State<'_, Arc<TaskManager>>) -> Result<Vec<Task>, String> {
+    Ok(task_manager.get_orphans())
+}
+
+#[tauri::command]
+pub async fn critical_path(
+    root_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<usize>, String> {
+    task_manager.critical_path(root_id)
+}
+
+#[tauri::command]
+pub async fn set_auto_complete_parents(
+    enabled: bool,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.set_auto_complete_parents(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn export_state_json(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<String, String> {
+    task_manager.export_state_json()
+}
+
+#[tauri::command]
+pub async fn set_recurrence(
+    id: usize,
+    recurrence: Option<Recurrence>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.set_recurrence(id, recurrence)
+}
+
+#[tauri::command]
+pub async fn preview_occurrences(
+    id: usize,
+    count: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<i64>, String> {
+    task_manager.preview_occurrences(id, count)
+}
+
+#[tauri::command]
+pub async fn find_cycles(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Vec<usize>>, String> {
+    Ok(task_manager.find_cycles())
+}
+
+#[tauri::command]
+pub async fn remove_tasks(
+    ids: Vec<usize>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<usize>, String> {
+    task_manager.remove_tasks(ids)
+}
+
+#[tauri::command]
+pub async fn get_subtasks_sorted(
+    id: usize,
+    sort: Option<SubtaskSort>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    task_manager.get_subtasks_sorted(id, sort.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn validate_reorder(
+    parent_id: usize,
+    new_order: Vec<usize>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<String>, String> {
+    task_manager.validate_reorder(parent_id, new_order)
+}
+
+#[tauri::command]
+pub async fn incomplete_counts_by_root(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<HashMap<usize, usize>, String> {
+    Ok(task_manager.incomplete_counts_by_root())
+}
+
+#[tauri::command]
+pub async fn delete_keep_children(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.delete_keep_children(id)
+}
+
+#[tauri::command]
+pub async fn lock_task(id: usize, task_manager: State<'_, Arc<TaskManager>>) -> Result<(), String> {
+    task_manager.lock_task(id)
+}
+
+#[tauri::command]
+pub async fn unlock_task(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.unlock_task(id)
+}
+
+#[tauri::command]
+pub async fn estimate_finish_date(
+    root_id: usize,
+    tasks_per_day: f32,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<i64, String> {
+    task_manager.estimate_finish_date(root_id, tasks_per_day)
+}
+
+#[tauri::command]
+pub async fn swap_tasks(
+    a: usize,
+    b: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.swap_tasks(a, b)
+}
+
+#[tauri::command]
+pub async fn get_effective_tags(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<String>, String> {
+    task_manager.get_effective_tags(id)
+}
+
+#[tauri::command]
+pub async fn get_tasks_by_tag(
+    tag: String,
+    include_inherited: Option<bool>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.get_tasks_by_tag(&tag, include_inherited.unwrap_or(false)))
+}
+
+#[tauri::command]
+pub async fn deepest_active_leaf(
+    root_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Option<Task>, String> {
+    task_manager.deepest_active_leaf(root_id)
+}
+
+#[tauri::command]
+pub async fn time_report(
+    from: i64,
+    to: i64,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<(usize, u32)>, String> {
+    Ok(task_manager.time_report(from, to))
+}
+
+#[tauri::command]
+pub async fn get_task_by_text(
+    text: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.get_task_by_text(&text))
+}
+
+#[tauri::command]
+pub async fn dump_structure(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<TaskDump>, String> {
+    Ok(task_manager.dump_structure())
+}
+
+#[tauri::command]
+pub async fn complete_task_with_predecessors(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.complete_task_with_predecessors(id)
+}
+
+#[tauri::command]
+pub async fn reset_subtree(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<usize, String> {
+    task_manager.reset_subtree(id)
+}
+
+#[tauri::command]
+pub async fn get_active_ids(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<usize>, String> {
+    Ok(task_manager.get_active_ids())
+}
+
+#[tauri::command]
+pub async fn add_comment(
+    id: usize,
+    text: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.add_comment(id, text, current_timestamp())
+}
+
+#[tauri::command]
+pub async fn get_comments(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Comment>, String> {
+    task_manager.get_comments(id)
+}
+
+#[tauri::command]
+pub async fn blocked_count(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<usize, String> {
+    task_manager.blocked_count(id)
+}
+
+#[tauri::command]
+pub async fn tag_subtree(
+    root_id: usize,
+    tag: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<usize, String> {
+    task_manager.tag_subtree(root_id, tag)
+}
+
+#[tauri::command]
+pub async fn newly_overdue(
+    previous_now: i64,
+    now: i64,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.newly_overdue(previous_now, now))
+}
+
+#[tauri::command]
+pub async fn archive_to_file(
+    root_id: usize,
+    path: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<usize, String> {
+    task_manager.archive_to_file(root_id, &path)
+}
+
+#[tauri::command]
+pub async fn get_recent_tasks(
+    limit: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.get_recent_tasks(limit))
+}
+
+#[tauri::command]
+pub async fn freeze_order_as_dependencies(
+    parent_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.freeze_order_as_dependencies(parent_id)
+}
+
+#[tauri::command]
+pub async fn has_unsaved_changes(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<bool, String> {
+    Ok(task_manager.has_unsaved_changes())
+}
+
+#[tauri::command]
+pub async fn get_all_tags(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<String>, String> {
+    Ok(task_manager.get_all_tags())
+}
+
+#[tauri::command]
+pub async fn move_after(
+    task_id: usize,
+    anchor_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.move_after(task_id, anchor_id)
+}
+
+#[tauri::command]
+pub async fn move_before(
+    task_id: usize,
+    anchor_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.move_before(task_id, anchor_id)
+}
+
+#[tauri::command]
+pub async fn ready_queue(task_manager: State<'_, Arc<TaskManager>>) -> Result<Vec<Task>, String> {
+    Ok(task_manager.ready_queue())
+}
+
+#[tauri::command]
+pub async fn duplicate_task_shallow(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<usize, String> {
+    task_manager.duplicate_task_shallow(id)
+}
+
+#[tauri::command]
+pub async fn get_root_tasks(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.get_root_tasks())
+}
+
+#[tauri::command]
+pub async fn sort_roots(
+    by: RootSort,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.sort_roots(by);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn find_duplicates(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Vec<usize>>, String> {
+    Ok(task_manager.find_duplicates())
+}
+
+#[tauri::command]
+pub async fn merge_tasks(
+    keep_id: usize,
+    merge_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.merge_tasks(keep_id, merge_id)
+}
+
+#[tauri::command]
+pub async fn export_ics(task_manager: State<'_, Arc<TaskManager>>) -> Result<String, String> {
+    Ok(task_manager.export_ics())
+}
+
+#[tauri::command]
+pub async fn all_complete(task_manager: State<'_, Arc<TaskManager>>) -> Result<bool, String> {
+    Ok(task_manager.all_complete())
+}
+
+#[tauri::command]
+pub async fn set_sort_key(
+    id: usize,
+    sort_key: Option<f64>,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.set_sort_key(id, sort_key)
+}
+
+#[tauri::command]
+pub async fn next_sibling_active(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Option<Task>, String> {
+    task_manager.next_sibling_active(id)
+}
+
+#[tauri::command]
+pub async fn set_priority_bulk(
+    ids: Vec<usize>,
+    priority: u8,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<usize, String> {
+    task_manager.set_priority_bulk(ids, priority)
+}
+
+#[tauri::command]
+pub async fn remaining_tasks(
+    root_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    task_manager.remaining_tasks(root_id)
+}
+
+#[tauri::command]
+pub async fn get_position(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(usize, usize), String> {
+    task_manager.get_position(id)
+}
+
+#[tauri::command]
+pub async fn set_dependency(
+    id: usize,
+    predecessor_id: usize,
+    hard: bool,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.set_dependency(id, predecessor_id, hard)
+}
+
+#[tauri::command]
+pub async fn stats_by_tag(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<HashMap<String, TaskStats>, String> {
+    Ok(task_manager.stats_by_tag())
+}
+
+#[tauri::command]
+pub async fn move_subtasks(
+    parent_id: Option<usize>,
+    ids: Vec<usize>,
+    to_index: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.move_subtasks(parent_id, ids, to_index)
+}
+
+#[tauri::command]
+pub async fn subtree_completion_timeline(
+    root_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<(i64, usize)>, String> {
+    Ok(task_manager.subtree_completion_timeline(root_id))
+}
+
+#[tauri::command]
+pub async fn clone_project(
+    root_id: usize,
+    new_name: String,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<usize, String> {
+    task_manager.clone_project(root_id, new_name)
+}
+
+#[tauri::command]
+pub async fn tasks_completed_in_range(
+    from: i64,
+    to: i64,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.tasks_completed_in_range(from, to))
+}
+
+#[tauri::command]
+pub async fn completions_between(
+    from: i64,
+    to: i64,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<(usize, i64)>, String> {
+    Ok(task_manager.completions_between(from, to))
+}
+
+#[tauri::command]
+pub async fn tasks_created_in_range(
+    from: i64,
+    to: i64,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<Task>, String> {
+    Ok(task_manager.tasks_created_in_range(from, to))
+}
+
+#[tauri::command]
+pub async fn collapse_subtasks(
+    parent_id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<(), String> {
+    task_manager.collapse_subtasks(parent_id)
+}
+
+#[tauri::command]
+pub async fn check_integrity(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<String>, String> {
+    Ok(task_manager.check_integrity())
+}
+
+#[tauri::command]
+pub async fn validate_and_repair(
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<Vec<String>, String> {
+    Ok(task_manager.validate_and_repair())
+}
+
+#[tauri::command]
+pub async fn get_task_context(
+    id: usize,
+    task_manager: State<'_, Arc<TaskManager>>,
+) -> Result<TaskContext, String> {
+    task_manager.get_task_context(id)
+}
@@ -1,12 +1,18 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::HashMap;
 use std::collections::HashSet;
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Task {
     id: usize,
     text: String,
@@ -14,6 +20,39 @@ pub struct Task {
     ordered: bool,
     subtasks: Vec<usize>,
     parent: Option<usize>,
+    #[serde(default)]
+    predecessors: Vec<Dependency>,
+    #[serde(default)]
+    waiting: Option<String>,
+    #[serde(default)]
+    completed_at: Option<i64>,
+    #[serde(default)]
+    priority: u8,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    due_date: Option<i64>,
+    #[serde(default)]
+    attachments: Vec<String>,
+    #[serde(default)]
+    in_progress: bool,
+    #[serde(default)]
+    auto_completed: bool,
+    #[serde(default)]
+    recurrence: Option<Recurrence>,
+    #[serde(default)]
+    locked: bool,
+    #[serde(default)]
+    started_at: Option<i64>,
+    #[serde(default)]
+    work_sessions: Vec<(i64, i64)>,
+    #[serde(default)]
+    comments: Vec<Comment>,
+    #[serde(default)]
+    sort_key: Option<f64>,
+    /// Unknown for tasks saved before creation times were recorded.
+    #[serde(default)]
+    created_at: Option<i64>,
 }
 
 impl Task {
@@ -25,79 +64,983 @@ impl Task {
             ordered,
             subtasks: Vec::new(),
             parent: None,
+            predecessors: Vec::new(),
+            waiting: None,
+            completed_at: None,
+            priority: 0,
+            tags: Vec::new(),
+            due_date: None,
+            attachments: Vec::new(),
+            in_progress: false,
+            auto_completed: false,
+            recurrence: None,
+            locked: false,
+            started_at: None,
+            work_sessions: Vec::new(),
+            comments: Vec::new(),
+            sort_key: None,
+            created_at: Some(current_timestamp()),
         }
     }
+
+    // Ends the running timer, if any, and records the session
+    fn stop_timer(&mut self, timestamp: i64) {
+        if let Some(started_at) = self.started_at.take() {
+            self.work_sessions
+                .push((started_at, timestamp.max(started_at)));
+        }
+        self.in_progress = false;
+    }
+
+    // Ids of the predecessors that block this task
+    fn hard_predecessors(&self) -> impl Iterator<Item = usize> + '_ {
+        self.predecessors
+            .iter()
+            .filter(|dependency| dependency.hard)
+            .map(|dependency| dependency.id)
+    }
+
+    // Adds a dependency, turning an existing soft one hard if `hard` is set
+    fn add_dependency(&mut self, id: usize, hard: bool) {
+        match self
+            .predecessors
+            .iter_mut()
+            .find(|dependency| dependency.id == id)
+        {
+            Some(dependency) => dependency.hard |= hard,
+            None => self.predecessors.push(Dependency { id, hard }),
+        }
+    }
+}
+
+/// A link to a task that should be done first. Only hard dependencies block activation;
+/// soft ones are informational.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    pub id: usize,
+    pub hard: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub text: String,
+    pub timestamp: i64,
+}
+
+/// Seconds since the Unix epoch.
+pub fn current_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+// Days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// `YYYYMMDD` as used by iCalendar DATE values
+fn ics_date(timestamp: i64) -> String {
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(86_400));
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+fn ics_escape(text: &str) -> String {
+    // A stray CR would end the content line early, so every line break becomes `\n`
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// Content lines longer than 75 octets continue on the next line after a space
+// (RFC 5545 section 3.1), never splitting a UTF-8 character
+fn ics_fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+/// Parses `YYYY-MM-DD` into a timestamp at midnight UTC.
+pub fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days past the end of the month, like 2024-02-31, roll over into the next one
+    let days = days_from_civil(year, month, day);
+    if civil_from_days(days) != (year, month, day) {
+        return None;
+    }
+    Some(days * 86_400)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TaskEvent {
+    Added(usize),
+    Completed(usize),
+    Removed(usize),
+    Reordered(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recurrence {
+    Daily,
+    Weekly,
+    EveryDays(u32),
+}
+
+impl Recurrence {
+    fn interval_days(self) -> u32 {
+        match self {
+            Recurrence::Daily => 1,
+            Recurrence::Weekly => 7,
+            Recurrence::EveryDays(days) => days,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum SubtaskSort {
+    #[default]
+    Manual,
+    IncompleteFirst,
+    DueDate,
+    Priority,
+}
+
+/// Auto-save decision: with unsaved changes, save when the regular interval is up,
+/// or earlier once a change has waited `max_defer`.
+pub fn should_autosave(
+    unsaved_for: Option<Duration>,
+    since_interval: Duration,
+    interval: Duration,
+    max_defer: Duration,
+) -> bool {
+    unsaved_for.is_some_and(|waiting| since_interval >= interval || waiting >= max_defer)
+}
+
+/// Schema version written into save files; older builds refuse newer files.
+pub const DATA_VERSION: u32 = 1;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum RootSort {
+    #[default]
+    Manual,
+    Alphabetical,
+    ProgressAscending,
+    DueDate,
+}
+
+pub const DEFAULT_MAX_TEXT_LENGTH: usize = 10_000;
+
+pub type Subscriber = Box<dyn Fn(&TaskEvent) + Send + Sync>;
+
+/// Optional constraints combined with AND, unset fields match everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TaskFilter {
+    pub completed: Option<bool>,
+    pub ordered: Option<bool>,
+    pub min_priority: Option<u8>,
+    pub has_tag: Option<String>,
+    pub due_before: Option<i64>,
+}
+
+impl TaskFilter {
+    fn matches(&self, task: &Task) -> bool {
+        self.completed.is_none_or(|c| task.completed == c)
+            && self.ordered.is_none_or(|o| task.ordered == o)
+            && self.min_priority.is_none_or(|p| task.priority >= p)
+            && self
+                .has_tag
+                .as_ref()
+                .is_none_or(|tag| task.tags.contains(tag))
+            && self
+                .due_before
+                .is_none_or(|due| task.due_date.is_some_and(|d| d < due))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TaskStats {
+    pub total: usize,
+    pub todo: usize,
+    pub in_progress: usize,
+    pub completed: usize,
+    pub active: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum EdgeKind {
+    Predecessor,
+    Ordered,
+    Parent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphData {
+    pub nodes: Vec<(usize, String, bool)>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// The raw structural fields of a task, for bug reports.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskDump {
+    pub id: usize,
+    pub text: String,
+    pub completed: bool,
+    pub ordered: bool,
+    pub parent: Option<usize>,
+    pub subtasks: Vec<usize>,
+    pub predecessors: Vec<Dependency>,
+}
+
+/// A task with its neighbors, fetched in one call for the swipe view.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskContext {
+    pub task: Task,
+    pub previous: Option<Task>,
+    pub next: Option<Task>,
+    pub parent: Option<Task>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateNode {
+    text: String,
+    ordered: bool,
+    subtasks: Vec<TemplateNode>,
+}
+
+// The flags the active-set walk needs from a task
+#[derive(Clone, Copy)]
+struct ActiveFields {
+    completed: bool,
+    ordered: bool,
+    waiting: bool,
+}
+
+// Lets the active-set walk run over a snapshot or over the live, individually locked tasks
+trait TaskLookup {
+    // Calls `f` with the task's flags, subtasks and predecessors, `None` if it doesn't exist
+    fn read<R>(
+        &self,
+        id: usize,
+        f: impl FnOnce(ActiveFields, &[usize], &[Dependency]) -> R,
+    ) -> Option<R>;
+}
+
+fn active_fields(task: &Task) -> ActiveFields {
+    ActiveFields {
+        completed: task.completed,
+        ordered: task.ordered,
+        waiting: task.waiting.is_some(),
+    }
+}
+
+impl TaskLookup for HashMap<usize, Task> {
+    fn read<R>(
+        &self,
+        id: usize,
+        f: impl FnOnce(ActiveFields, &[usize], &[Dependency]) -> R,
+    ) -> Option<R> {
+        self.get(&id)
+            .map(|task| f(active_fields(task), &task.subtasks, &task.predecessors))
+    }
+}
+
+// Holds the map lock only for the lookup, then just the one task's lock
+impl TaskLookup for Mutex<HashMap<usize, Arc<Mutex<Task>>>> {
+    fn read<R>(
+        &self,
+        id: usize,
+        f: impl FnOnce(ActiveFields, &[usize], &[Dependency]) -> R,
+    ) -> Option<R> {
+        let task = self.lock().unwrap().get(&id).cloned()?;
+        let task = task.lock().unwrap();
+        Some(f(active_fields(&task), &task.subtasks, &task.predecessors))
+    }
+}
+
+// Tarjan's strongly connected components over the predecessor graph
+struct CycleFinder<'a> {
+    edges: &'a HashMap<usize, Vec<usize>>,
+    next_index: usize,
+    index: HashMap<usize, usize>,
+    lowlink: HashMap<usize, usize>,
+    stack: Vec<usize>,
+    on_stack: HashSet<usize>,
+    components: Vec<Vec<usize>>,
+}
+
+impl CycleFinder<'_> {
+    fn open(&mut self, id: usize) {
+        self.index.insert(id, self.next_index);
+        self.lowlink.insert(id, self.next_index);
+        self.next_index += 1;
+        self.stack.push(id);
+        self.on_stack.insert(id);
+    }
+
+    // Walks with an explicit stack of (task, next edge), so a long dependency chain
+    // cannot overflow the call stack
+    fn visit(&mut self, root: usize) {
+        self.open(root);
+        let mut work = vec![(root, 0)];
+
+        while let Some(&(id, edge)) = work.last() {
+            match self.edges.get(&id).and_then(|edges| edges.get(edge)) {
+                Some(&next) => {
+                    work.last_mut().unwrap().1 += 1;
+                    if !self.index.contains_key(&next) {
+                        self.open(next);
+                        work.push((next, 0));
+                    } else if self.on_stack.contains(&next) {
+                        let low = self.lowlink[&id].min(self.index[&next]);
+                        self.lowlink.insert(id, low);
+                    }
+                }
+                None => {
+                    work.pop();
+                    if let Some(&(caller, _)) = work.last() {
+                        let low = self.lowlink[&caller].min(self.lowlink[&id]);
+                        self.lowlink.insert(caller, low);
+                    }
+
+                    if self.lowlink[&id] == self.index[&id] {
+                        let mut component = Vec::new();
+                        while let Some(member) = self.stack.pop() {
+                            self.on_stack.remove(&member);
+                            component.push(member);
+                            if member == id {
+                                break;
+                            }
+                        }
+                        self.components.push(component);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A task as stored in the save file, which keeps snake_case keys
+/// while `Task` itself serializes camelCase for the frontend.
+#[derive(Serialize, Deserialize)]
+struct TaskRecord {
+    id: usize,
+    text: String,
+    completed: bool,
+    ordered: bool,
+    subtasks: Vec<usize>,
+    parent: Option<usize>,
+    #[serde(default)]
+    predecessors: Vec<DependencyRecord>,
+    #[serde(default)]
+    waiting: Option<String>,
+    #[serde(default)]
+    completed_at: Option<i64>,
+    #[serde(default)]
+    priority: u8,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    due_date: Option<i64>,
+    #[serde(default)]
+    attachments: Vec<String>,
+    #[serde(default)]
+    in_progress: bool,
+    #[serde(default)]
+    auto_completed: bool,
+    #[serde(default)]
+    recurrence: Option<Recurrence>,
+    #[serde(default)]
+    locked: bool,
+    #[serde(default)]
+    started_at: Option<i64>,
+    #[serde(default)]
+    work_sessions: Vec<(i64, i64)>,
+    #[serde(default)]
+    comments: Vec<Comment>,
+    #[serde(default)]
+    sort_key: Option<f64>,
+    // Soft dependencies as briefly saved in their own list, read only for migration
+    #[serde(default, skip_serializing)]
+    soft_predecessors: Vec<usize>,
+    #[serde(default)]
+    created_at: Option<i64>,
+}
+
+impl From<Task> for TaskRecord {
+    fn from(task: Task) -> Self {
+        TaskRecord {
+            id: task.id,
+            text: task.text,
+            completed: task.completed,
+            ordered: task.ordered,
+            subtasks: task.subtasks,
+            parent: task.parent,
+            predecessors: task
+                .predecessors
+                .into_iter()
+                .map(DependencyRecord::Full)
+                .collect(),
+            waiting: task.waiting,
+            completed_at: task.completed_at,
+            priority: task.priority,
+            tags: task.tags,
+            due_date: task.due_date,
+            attachments: task.attachments,
+            in_progress: task.in_progress,
+            auto_completed: task.auto_completed,
+            recurrence: task.recurrence,
+            locked: task.locked,
+            started_at: task.started_at,
+            work_sessions: task.work_sessions,
+            comments: task.comments,
+            sort_key: task.sort_key,
+            soft_predecessors: Vec::new(),
+            created_at: task.created_at,
+        }
+    }
+}
+
+impl From<TaskRecord> for Task {
+    fn from(task: TaskRecord) -> Self {
+        Task {
+            id: task.id,
+            text: task.text,
+            completed: task.completed,
+            ordered: task.ordered,
+            subtasks: task.subtasks,
+            parent: task.parent,
+            predecessors: task
+                .predecessors
+                .into_iter()
+                .map(|dependency| match dependency {
+                    DependencyRecord::Id(id) => Dependency { id, hard: true },
+                    DependencyRecord::Full(dependency) => dependency,
+                })
+                .chain(
+                    task.soft_predecessors
+                        .into_iter()
+                        .map(|id| Dependency { id, hard: false }),
+                )
+                .collect(),
+            waiting: task.waiting,
+            completed_at: task.completed_at,
+            priority: task.priority,
+            tags: task.tags,
+            due_date: task.due_date,
+            attachments: task.attachments,
+            in_progress: task.in_progress,
+            auto_completed: task.auto_completed,
+            recurrence: task.recurrence,
+            locked: task.locked,
+            started_at: task.started_at,
+            work_sessions: task.work_sessions,
+            comments: task.comments,
+            sort_key: task.sort_key,
+            created_at: task.created_at,
+        }
+    }
+}
+
+/// A stored dependency. Files written before soft dependencies list bare ids, which
+/// are all hard.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum DependencyRecord {
+    Id(usize),
+    Full(Dependency),
 }
 
 #[derive(Serialize, Deserialize)]
 struct TaskManagerData {
-    tasks: Vec<Task>,
+    #[serde(default)]
+    version: u32,
+    tasks: Vec<TaskRecord>,
     root_tasks: Vec<usize>,
     next_id: usize,
+    #[serde(default)]
+    templates: BTreeMap<String, TemplateNode>,
 }
 
 pub struct TaskManager {
     pub tasks: Mutex<HashMap<usize, Arc<Mutex<Task>>>>,
     root_tasks: Mutex<Vec<usize>>,
     next_id: Mutex<usize>,
+    subscribers: Mutex<Vec<Subscriber>>,
+    max_tasks: Mutex<Option<usize>>,
+    templates: Mutex<HashMap<String, TemplateNode>>,
+    focus: Mutex<Option<Vec<Task>>>,
+    ephemeral: bool,
+    max_text_length: Mutex<usize>,
+    dirty: Mutex<bool>,
+    dirty_since: Mutex<Option<Instant>>,
+    repair_on_load: Mutex<bool>,
+    load_repairs: Mutex<Vec<String>>,
+    auto_complete_parents: Mutex<bool>,
+}
+
+impl Default for TaskManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TaskManager {
     pub fn new() -> Self {
+        Self::with_persistence(false)
+    }
+
+    /// A manager that never touches disk, saving becomes a no-op.
+    pub fn new_ephemeral() -> Self {
+        Self::with_persistence(true)
+    }
+
+    fn with_persistence(ephemeral: bool) -> Self {
         TaskManager {
             tasks: Mutex::new(HashMap::new()),
             root_tasks: Mutex::new(Vec::new()),
             next_id: Mutex::new(1),
+            subscribers: Mutex::new(Vec::new()),
+            max_tasks: Mutex::new(None),
+            templates: Mutex::new(HashMap::new()),
+            focus: Mutex::new(None),
+            ephemeral,
+            max_text_length: Mutex::new(DEFAULT_MAX_TEXT_LENGTH),
+            dirty: Mutex::new(false),
+            dirty_since: Mutex::new(None),
+            repair_on_load: Mutex::new(true),
+            load_repairs: Mutex::new(Vec::new()),
+            auto_complete_parents: Mutex::new(false),
         }
     }
 
-    pub fn save_to_file(&self, file_path: &str) -> Result<(), String> {
+    /// Registers a callback that is invoked after every mutation.
+    pub fn subscribe(&self, callback: Subscriber) {
+        self.subscribers.lock().unwrap().push(callback);
+    }
+
+    // Must be called without holding any task locks, subscribers may read back.
+    fn notify(&self, event: TaskEvent) {
+        let subscribers = self.subscribers.lock().unwrap();
+        for subscriber in subscribers.iter() {
+            subscriber(&event);
+        }
+    }
+
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    fn state_data(&self) -> TaskManagerData {
         let tasks = self.tasks.lock().unwrap();
         let root_tasks = self.root_tasks.lock().unwrap();
         let next_id = *self.next_id.lock().unwrap();
 
-        let task_data: Vec<Task> = tasks
+        let mut task_data: Vec<TaskRecord> = tasks
             .values()
-            .map(|task_arc| task_arc.lock().unwrap().clone())
+            .map(|task_arc| task_arc.lock().unwrap().clone().into())
             .collect();
+        task_data.sort_by_key(|task| task.id);
 
-        let data = TaskManagerData {
+        TaskManagerData {
+            version: DATA_VERSION,
             tasks: task_data,
             root_tasks: root_tasks.clone(),
             next_id,
-        };
+            templates: self
+                .templates
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, node)| (name.clone(), node.clone()))
+                .collect(),
+        }
+    }
+
+    /// The exact contents `save_to_file` would write, without touching disk.
+    pub fn export_state_json(&self) -> Result<String, String> {
+        // Pretty and sorted by id so the file diffs cleanly between saves
+        serde_json::to_string_pretty(&self.state_data())
+            .map_err(|e| format!("Failed to serialize data: {}", e))
+    }
+
+    pub fn save_to_file(&self, file_path: &str) -> Result<(), String> {
+        if self.ephemeral {
+            return Ok(());
+        }
+
+        let json = self.export_state_json()?;
 
-        let file = File::create(file_path).map_err(|e| format!("Failed to create file: {}", e))?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer(writer, &data)
-            .map_err(|e| format!("Failed to write data to file: {}", e))?;
+        if let Some(parent_dir) = Path::new(file_path).parent() {
+            fs::create_dir_all(parent_dir).map_err(|e| {
+                format!("Failed to create directory {}: {}", parent_dir.display(), e)
+            })?;
+        }
+
+        // A `.gz` path opts into compression, anything else stays plain JSON
+        let bytes = if file_path.ends_with(".gz") {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(json.as_bytes())
+                .and_then(|_| encoder.finish())
+                .map_err(|e| format!("Failed to compress data: {}", e))?
+        } else {
+            json.into_bytes()
+        };
+        fs::write(file_path, bytes).map_err(|e| format!("Failed to write data to file: {}", e))?;
 
+        self.mark_clean();
         Ok(())
     }
 
     pub fn load_from_file(&self, file_path: &str) -> Result<(), String> {
         let file = File::open(file_path).map_err(|e| format!("Failed to open file: {}", e))?;
-        let reader = BufReader::new(file);
+        let mut reader = BufReader::new(file);
+
+        // Compressed files are recognized by the gzip magic bytes, whatever their name
+        let is_gzip = reader
+            .fill_buf()
+            .map_err(|e| format!("Failed to read data from file: {}", e))?
+            .starts_with(&GZIP_MAGIC);
+        let value: serde_json::Value = if is_gzip {
+            serde_json::from_reader(GzDecoder::new(reader))
+        } else {
+            serde_json::from_reader(reader)
+        }
+        .map_err(|e| format!("Failed to read data from file: {}", e))?;
+
+        // Parsing a newer file would silently drop the fields this build doesn't know
+        if let Some(version) = value.get("version").and_then(|v| v.as_u64()) {
+            if version > u64::from(DATA_VERSION) {
+                return Err(format!(
+                    "FutureVersion: data file version {} is newer than supported version {}",
+                    version, DATA_VERSION
+                ));
+            }
+        }
 
-        let data: TaskManagerData = serde_json::from_reader(reader)
+        let data: TaskManagerData = serde_json::from_value(value)
             .map_err(|e| format!("Failed to read data from file: {}", e))?;
 
-        let mut tasks_map = self.tasks.lock().unwrap();
-        let mut root_task_ids = self.root_tasks.lock().unwrap();
-        let mut next_id = self.next_id.lock().unwrap();
+        let mut loaded_tasks: HashMap<usize, Task> = data
+            .tasks
+            .into_iter()
+            .map(|record| {
+                let task = Task::from(record);
+                (task.id, task)
+            })
+            .collect();
+        let mut loaded_roots = data.root_tasks;
+        let mut loaded_next_id = data.next_id;
+
+        // The file is checked before it replaces anything, so a strict load that fails
+        // leaves the current tasks as they were. Otherwise a damaged file is repaired
+        // right away rather than panicking somewhere later.
+        let repairs =
+            Self::repair_structure(&mut loaded_tasks, &mut loaded_roots, &mut loaded_next_id);
+        if !repairs.is_empty() && !*self.repair_on_load.lock().unwrap() {
+            return Err(format!("Integrity check failed: {}", repairs.join("; ")));
+        }
+
+        {
+            let mut tasks_map = self.tasks.lock().unwrap();
+            let mut root_task_ids = self.root_tasks.lock().unwrap();
+            let mut next_id = self.next_id.lock().unwrap();
+
+            *tasks_map = loaded_tasks
+                .into_iter()
+                .map(|(id, task)| (id, Arc::new(Mutex::new(task))))
+                .collect();
+            *root_task_ids = loaded_roots;
+            *next_id = loaded_next_id;
+            *self.templates.lock().unwrap() = data.templates.into_iter().collect();
+        }
+        self.mark_clean();
+        // Repairs only exist in memory until the next save
+        if !repairs.is_empty() {
+            self.mark_dirty();
+        }
+        *self.load_repairs.lock().unwrap() = repairs;
+
+        Ok(())
+    }
+
+    /// Strict environments can turn off the repair after loading, making a damaged
+    /// file fail the load instead.
+    pub fn set_repair_on_load(&self, enabled: bool) {
+        *self.repair_on_load.lock().unwrap() = enabled;
+    }
+
+    /// What the last load had to repair, empty when the file was consistent.
+    pub fn load_repairs(&self) -> Vec<String> {
+        self.load_repairs.lock().unwrap().clone()
+    }
+
+    /// Lists structural problems: dangling ids, parent links that disagree with
+    /// subtask lists, tasks missing from or unreachable through the root list.
+    pub fn check_integrity(&self) -> Vec<String> {
+        self.integrity_pass(false)
+    }
+
+    /// Fixes what `check_integrity` reports and returns a line per repair.
+    pub fn validate_and_repair(&self) -> Vec<String> {
+        self.integrity_pass(true)
+    }
+
+    // Repairs are always applied to a snapshot; they are only written back when `repair` is set
+    fn integrity_pass(&self, repair: bool) -> Vec<String> {
+        let mut tasks_map = self.snapshot();
+        let mut root_tasks = self.root_tasks.lock().unwrap().clone();
+        let mut next_id = *self.next_id.lock().unwrap();
+        let problems = Self::repair_structure(&mut tasks_map, &mut root_tasks, &mut next_id);
+
+        if repair && !problems.is_empty() {
+            {
+                let tasks = self.tasks.lock().unwrap();
+                let mut root_task_ids = self.root_tasks.lock().unwrap();
+                for (id, task) in tasks_map {
+                    if let Some(task_arc) = tasks.get(&id) {
+                        *task_arc.lock().unwrap() = task;
+                    }
+                }
+                *root_task_ids = root_tasks;
+                let mut current_next_id = self.next_id.lock().unwrap();
+                *current_next_id = (*current_next_id).max(next_id);
+            }
+            self.mark_dirty();
+        }
+        problems
+    }
+
+    // Fixes the given state in place and returns a line per problem found
+    fn repair_structure(
+        tasks_map: &mut HashMap<usize, Task>,
+        root_tasks: &mut Vec<usize>,
+        next_id: &mut usize,
+    ) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut ids: Vec<usize> = tasks_map.keys().copied().collect();
+        ids.sort();
+
+        for &id in &ids {
+            let task = tasks_map.get_mut(&id).unwrap();
+            if task.id != id {
+                problems.push(format!("Task stored under id {} had id {}", id, task.id));
+                task.id = id;
+            }
+        }
+
+        // Dangling and repeated references
+        for &id in &ids {
+            let task = &tasks_map[&id];
+            let mut seen = HashSet::new();
+            let subtasks: Vec<usize> = task
+                .subtasks
+                .iter()
+                .copied()
+                .filter(|sid| tasks_map.contains_key(sid) && seen.insert(*sid))
+                .collect();
+            let predecessors: Vec<Dependency> = task
+                .predecessors
+                .iter()
+                .copied()
+                .filter(|dependency| tasks_map.contains_key(&dependency.id))
+                .collect();
+
+            let task = tasks_map.get_mut(&id).unwrap();
+            if subtasks != task.subtasks {
+                problems.push(format!("Task {} listed missing or repeated subtasks", id));
+                task.subtasks = subtasks;
+            }
+            if predecessors != task.predecessors {
+                problems.push(format!("Task {} depended on missing tasks", id));
+                task.predecessors = predecessors;
+            }
+        }
+
+        // Subtask lists decide where a task lives; a task listed twice stays with the first
+        let mut listed_under: HashMap<usize, usize> = HashMap::new();
+        for &id in &ids {
+            let subtasks = tasks_map[&id].subtasks.clone();
+            let mut kept = Vec::new();
+            for sid in subtasks {
+                if sid == id || listed_under.contains_key(&sid) {
+                    problems.push(format!(
+                        "Task {} was listed under more than one parent",
+                        sid
+                    ));
+                } else {
+                    listed_under.insert(sid, id);
+                    kept.push(sid);
+                }
+            }
+            tasks_map.get_mut(&id).unwrap().subtasks = kept;
+        }
+
+        for &id in &ids {
+            let parent = tasks_map[&id].parent;
+            match listed_under.get(&id) {
+                Some(&listed) if parent != Some(listed) => {
+                    problems.push(format!("Task {} pointed at the wrong parent", id));
+                    tasks_map.get_mut(&id).unwrap().parent = Some(listed);
+                }
+                Some(_) => {}
+                None => match parent {
+                    Some(pid) if tasks_map.contains_key(&pid) && pid != id => {
+                        problems.push(format!(
+                            "Task {} was missing from its parent's subtasks",
+                            id
+                        ));
+                        tasks_map.get_mut(&pid).unwrap().subtasks.push(id);
+                        listed_under.insert(id, pid);
+                    }
+                    Some(_) => {
+                        problems.push(format!("Task {} had a missing parent", id));
+                        tasks_map.get_mut(&id).unwrap().parent = None;
+                    }
+                    None => {}
+                },
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let valid_roots: Vec<usize> = root_tasks
+            .iter()
+            .copied()
+            .filter(|id| tasks_map.get(id).is_some_and(|t| t.parent.is_none()) && seen.insert(*id))
+            .collect();
+        if valid_roots != *root_tasks {
+            problems.push("Root list had missing, repeated or nested tasks".to_string());
+            *root_tasks = valid_roots;
+        }
+        for &id in &ids {
+            if tasks_map[&id].parent.is_none() && !root_tasks.contains(&id) {
+                problems.push(format!("Task {} was missing from the root list", id));
+                root_tasks.push(id);
+            }
+        }
 
-        tasks_map.clear();
-        root_task_ids.clear();
+        // Parent loops are cut at their lowest id, which becomes a root
+        loop {
+            let mut reachable = HashSet::new();
+            for &root_id in root_tasks.iter() {
+                reachable.extend(Self::subtree_ids(tasks_map, root_id));
+            }
+            let unreachable = match ids.iter().find(|id| !reachable.contains(id)) {
+                Some(&id) => id,
+                None => break,
+            };
+            problems.push(format!(
+                "Task {} was unreachable from any root",
+                unreachable
+            ));
+            if let Some(pid) = tasks_map[&unreachable].parent {
+                if let Some(parent) = tasks_map.get_mut(&pid) {
+                    parent.subtasks.retain(|&sid| sid != unreachable);
+                }
+            }
+            tasks_map.get_mut(&unreachable).unwrap().parent = None;
+            root_tasks.push(unreachable);
+        }
 
-        for task in data.tasks {
-            let task_id = task.id;
-            let task_arc = Arc::new(Mutex::new(task));
-            tasks_map.insert(task_id, task_arc);
+        let min_next_id = ids.last().map_or(1, |max| max + 1);
+        if *next_id < min_next_id {
+            problems.push(format!(
+                "Next id was behind the highest id {}",
+                min_next_id - 1
+            ));
+            *next_id = min_next_id;
         }
 
-        *root_task_ids = data.root_tasks;
-        *next_id = data.next_id;
+        problems
+    }
+
+    fn mark_dirty(&self) {
+        *self.dirty.lock().unwrap() = true;
+        self.dirty_since
+            .lock()
+            .unwrap()
+            .get_or_insert_with(Instant::now);
+    }
+
+    fn mark_clean(&self) {
+        *self.dirty.lock().unwrap() = false;
+        *self.dirty_since.lock().unwrap() = None;
+    }
+
+    /// How long the oldest unsaved change has been waiting, `None` when everything is saved.
+    pub fn unsaved_for(&self) -> Option<Duration> {
+        self.dirty_since
+            .lock()
+            .unwrap()
+            .map(|since| since.elapsed())
+    }
+
+    /// Saves only when something changed, returning whether the file was written.
+    pub fn save_if_dirty(&self, file_path: &str) -> Result<bool, String> {
+        if !self.has_unsaved_changes() {
+            return Ok(false);
+        }
+        self.save_to_file(file_path)?;
+        Ok(true)
+    }
 
+    /// Persists a single changed task. The JSON backend has no per-task storage,
+    /// so this marks the state dirty and the next save rewrites the whole file;
+    /// a database backend can write just this task instead.
+    pub fn upsert_task(&self, id: usize) -> Result<(), String> {
+        if !self.tasks.lock().unwrap().contains_key(&id) {
+            return Err(format!("Task with id: {} not found", id));
+        }
+        self.mark_dirty();
         Ok(())
     }
 
@@ -108,316 +1051,2937 @@ impl TaskManager {
         current_id
     }
 
-    pub fn add_task(&self, text: String, ordered: bool) -> usize {
-        let id = self.generate_id();
-        let task = Arc::new(Mutex::new(Task::new(id, text, ordered)));
+    /// Caps the total number of tasks, `None` means unlimited.
+    pub fn set_max_tasks(&self, max_tasks: Option<usize>) {
+        *self.max_tasks.lock().unwrap() = max_tasks;
+    }
 
-        {
-            let mut tasks = self.tasks.lock().unwrap();
-            tasks.insert(id, task);
+    /// When enabled, completing a parent's last open subtask completes the parent too.
+    pub fn set_auto_complete_parents(&self, enabled: bool) {
+        *self.auto_complete_parents.lock().unwrap() = enabled;
+    }
+
+    // Takes the locked map: `lock_task` needs it too, so nothing can lock the task
+    // between this check and the caller's edit under the same map lock
+    fn check_unlocked(tasks: &HashMap<usize, Arc<Mutex<Task>>>, id: usize) -> Result<(), String> {
+        match tasks.get(&id) {
+            Some(task) if task.lock().unwrap().locked => {
+                Err(format!("Task with id: {} is locked", id))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Takes the locked map so callers can check and insert without releasing it in between
+    fn check_task_limit(
+        &self,
+        tasks: &HashMap<usize, Arc<Mutex<Task>>>,
+        adding: usize,
+    ) -> Result<(), String> {
+        let max_tasks = *self.max_tasks.lock().unwrap();
+        if let Some(max) = max_tasks {
+            if tasks.len() + adding > max {
+                return Err(format!(
+                    "Task limit reached: cannot have more than {} tasks",
+                    max
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Limits task text length, counted in characters rather than bytes.
+    pub fn set_max_text_length(&self, max_length: usize) {
+        *self.max_text_length.lock().unwrap() = max_length;
+    }
+
+    fn check_text_length(&self, text: &str) -> Result<(), String> {
+        let max_length = *self.max_text_length.lock().unwrap();
+        let length = text.chars().count();
+        if length > max_length {
+            return Err(format!(
+                "Task text is too long: {} characters, at most {} allowed",
+                length, max_length
+            ));
         }
+        Ok(())
+    }
+
+    pub fn add_task(&self, text: String, ordered: bool) -> Result<usize, String> {
+        self.check_text_length(&text)?;
+        let id = {
+            let mut tasks = self.tasks.lock().unwrap();
+            self.check_task_limit(&tasks, 1)?;
+            let id = self.generate_id();
+            tasks.insert(id, Arc::new(Mutex::new(Task::new(id, text, ordered))));
+            id
+        };
 
         {
             let mut root_tasks = self.root_tasks.lock().unwrap();
             root_tasks.push(id);
         }
-        id
+
+        self.upsert_task(id)?;
+        self.notify(TaskEvent::Added(id));
+        Ok(id)
     }
 
     pub fn add_subtask(&self, parent_id: usize, text: String) -> Result<usize, String> {
-        let id = self.generate_id();
-        let subtask = Arc::new(Mutex::new(Task::new(id, text.clone(), true)));
+        self.check_text_length(&text)?;
 
-        let parent_task = {
-            let tasks = self.tasks.lock().unwrap();
-            tasks
+        // Hold the map lock from the parent lookup to the insert, so a concurrent
+        // remove cannot delete the parent in between and leave the subtask orphaned
+        let id = {
+            let mut tasks = self.tasks.lock().unwrap();
+            let parent = tasks
                 .get(&parent_id)
                 .ok_or(format!("Task with id: {} not found", parent_id))?
-                .clone()
+                .clone();
+            self.check_task_limit(&tasks, 1)?;
+            let id = self.generate_id();
+            let mut subtask = Task::new(id, text, true);
+            subtask.parent = Some(parent_id);
+            parent.lock().unwrap().subtasks.push(id);
+            tasks.insert(id, Arc::new(Mutex::new(subtask)));
+            id
         };
 
-        {
-            let mut subtask_lock = subtask.lock().unwrap();
-            subtask_lock.parent = Some(parent_id);
-        }
+        self.upsert_task(parent_id)?;
+        self.upsert_task(id)?;
+        self.notify(TaskEvent::Added(id));
+        Ok(id)
+    }
+
+    pub fn update_task_text(&self, id: usize, text: String) -> Result<(), String> {
+        self.check_text_length(&text)?;
+        self.with_unlocked_task(id, |task| task.text = text)
+    }
+
+    pub fn complete_task(&self, id: usize) -> Result<(), String> {
+        self.complete_task_at(id, current_timestamp())
+    }
 
+    pub fn complete_task_at(&self, id: usize, timestamp: i64) -> Result<(), String> {
+        let task = {
+            let tasks = self.tasks.lock().unwrap();
+            tasks
+                .get(&id)
+                .ok_or(format!("Task with id: {} not found", id))?
+                .clone()
+        };
         {
-            let mut parent_task_lock = parent_task.lock().unwrap();
-            parent_task_lock.subtasks.push(id);
+            let mut task_lock = task.lock().unwrap();
+            if task_lock.locked {
+                return Err(format!("Task with id: {} is locked", id));
+            }
+            task_lock.completed = true;
+            task_lock.completed_at = Some(timestamp);
+            task_lock.stop_timer(timestamp);
+            task_lock.auto_completed = false;
+        }
+        self.upsert_task(id)?;
+        self.notify(TaskEvent::Completed(id));
+
+        if *self.auto_complete_parents.lock().unwrap() {
+            self.complete_finished_ancestors(id, timestamp)?;
+        }
+        Ok(())
+    }
+
+    // Walks up from a just-completed task, closing each parent whose subtasks are now all done
+    fn complete_finished_ancestors(&self, id: usize, timestamp: i64) -> Result<(), String> {
+        let mut visited = HashSet::new();
+        let mut current_id = id;
+
+        while visited.insert(current_id) {
+            let parent_id = match self.get_task(current_id).and_then(|task| task.parent) {
+                Some(parent_id) => parent_id,
+                None => break,
+            };
+            let parent = match self.get_task(parent_id) {
+                Some(parent) => parent,
+                None => break,
+            };
+            let all_done = parent
+                .subtasks
+                .iter()
+                .filter_map(|sid| self.get_task(*sid))
+                .all(|subtask| subtask.completed);
+            if parent.completed || parent.locked || !all_done {
+                break;
+            }
+
+            self.with_task_mut(parent_id, |task| {
+                task.completed = true;
+                task.completed_at = Some(timestamp);
+                task.stop_timer(timestamp);
+                task.auto_completed = true;
+            })?;
+            self.notify(TaskEvent::Completed(parent_id));
+            current_id = parent_id;
+        }
+        Ok(())
+    }
+
+    /// With `reopen_ancestors`, also reopens parents that were only closed by auto-completion.
+    pub fn uncomplete_task(&self, id: usize, reopen_ancestors: bool) -> Result<(), String> {
+        self.with_unlocked_task(id, |task| {
+            task.completed = false;
+            task.completed_at = None;
+            task.auto_completed = false;
+        })?;
+        if !reopen_ancestors {
+            return Ok(());
+        }
+
+        let mut visited = HashSet::from([id]);
+        let mut parent_id = self.get_task(id).and_then(|task| task.parent);
+        while let Some(current_id) = parent_id {
+            let parent = match self.get_task(current_id) {
+                Some(parent) if parent.auto_completed && visited.insert(current_id) => parent,
+                _ => break,
+            };
+            // A parent locked since it was auto-completed stays closed, like in completion
+            if self
+                .with_unlocked_task(current_id, |task| {
+                    task.completed = false;
+                    task.completed_at = None;
+                    task.auto_completed = false;
+                })
+                .is_err()
+            {
+                break;
+            }
+            parent_id = parent.parent;
+        }
+        Ok(())
+    }
+
+    /// Returns how many subtask ids were skipped because they no longer exist.
+    pub fn toggle_ordered(&self, id: usize) -> Result<usize, String> {
+        let skipped = {
+            let tasks = self.tasks.lock().unwrap();
+            let task = tasks
+                .get(&id)
+                .ok_or(format!("Task with id: {} not found", id))?;
+            let mut task_lock = task.lock().unwrap();
+            if task_lock.locked {
+                return Err(format!("Task with id: {} is locked", id));
+            }
+            task_lock.ordered = !task_lock.ordered;
+
+            task_lock
+                .subtasks
+                .iter()
+                .filter(|sid| !tasks.contains_key(sid))
+                .count()
+        };
+
+        self.upsert_task(id)?;
+        Ok(skipped)
+    }
+
+    // Method to adjust the order of subtasks, returns false when the order is unchanged
+    pub fn reorder_subtasks(
+        &self,
+        parent_id: usize,
+        new_order: Vec<usize>,
+    ) -> Result<bool, String> {
+        let tasks_map = self.tasks.lock().unwrap();
+        let parent_task_arc = tasks_map
+            .get(&parent_id)
+            .ok_or(format!("Parent task with id: {} not found", parent_id))?
+            .clone();
+
+        let mut parent_task_lock = parent_task_arc.lock().unwrap();
+        if parent_task_lock.locked {
+            return Err(format!("Task with id: {} is locked", parent_id));
+        }
+
+        // Validate that new_order contains the same subtasks
+        let current_subtasks_set: HashSet<_> = parent_task_lock.subtasks.iter().cloned().collect();
+        let new_subtasks_set: HashSet<_> = new_order.iter().cloned().collect();
+        if current_subtasks_set != new_subtasks_set {
+            return Err("New order must contain the same subtasks".to_string());
+        }
+
+        if parent_task_lock.subtasks == new_order {
+            return Ok(false);
+        }
+
+        // Update the subtask order
+        parent_task_lock.subtasks = new_order.clone();
+        drop(parent_task_lock);
+        drop(tasks_map);
+
+        self.upsert_task(parent_id)?;
+        self.notify(TaskEvent::Reordered(parent_id));
+        Ok(true)
+    }
+
+    // 克隆任务映射，避免持有锁
+    fn snapshot(&self) -> HashMap<usize, Task> {
+        let tasks = self.tasks.lock().unwrap();
+        tasks
+            .iter()
+            .map(|(&id, task_arc)| {
+                let task_lock = task_arc.lock().unwrap();
+                (id, task_lock.clone())
+            })
+            .collect()
+    }
+
+    /// Returns the focus snapshot while a focus session is running.
+    pub fn get_active_tasks(&self) -> Vec<Task> {
+        if let Some(focus_set) = self.focus_set() {
+            return focus_set;
+        }
+        self.compute_active_tasks()
+    }
+
+    fn compute_active_tasks(&self) -> Vec<Task> {
+        let tasks_map = self.snapshot();
+        self.active_ids_in(&tasks_map)
+            .iter()
+            .map(|id| tasks_map[id].clone())
+            .collect()
+    }
+
+    fn active_ids_in(&self, tasks_map: &HashMap<usize, Task>) -> Vec<usize> {
+        let root_task_ids = {
+            let root_tasks = self.root_tasks.lock().unwrap();
+            root_tasks.clone()
+        };
+
+        let mut active_ids = Vec::new();
+        let mut visited = HashSet::new();
+
+        for root_task_id in root_task_ids {
+            Self::collect_active_ids(tasks_map, root_task_id, &mut visited, &mut active_ids);
+        }
+
+        active_ids
+    }
+
+    /// Ids of the active tasks in ascending order, for cheap diffing on the frontend.
+    pub fn get_active_ids(&self) -> Vec<usize> {
+        let mut active_ids: Vec<usize> = match self.focus_set() {
+            Some(focus_set) => focus_set.iter().map(|t| t.id).collect(),
+            None => {
+                // Reads the live tasks one at a time instead of cloning a snapshot
+                let root_task_ids = self.root_tasks.lock().unwrap().clone();
+                let mut active_ids = Vec::new();
+                let mut visited = HashSet::new();
+                for root_task_id in root_task_ids {
+                    Self::collect_active_ids(
+                        &self.tasks,
+                        root_task_id,
+                        &mut visited,
+                        &mut active_ids,
+                    );
+                }
+                active_ids
+            }
+        };
+        active_ids.sort();
+        active_ids
+    }
+
+    /// Active tasks within one root's subtree, still honoring predecessors outside it.
+    pub fn get_active_tasks_for_root(&self, root_id: usize) -> Result<Vec<Task>, String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&root_id) {
+            return Err(format!("Task with id: {} not found", root_id));
+        }
+
+        let mut active_ids = Vec::new();
+        let mut visited = HashSet::new();
+        Self::collect_active_ids(&tasks_map, root_id, &mut visited, &mut active_ids);
+        Ok(active_ids.iter().map(|id| tasks_map[id].clone()).collect())
+    }
+
+    // Walks the subtree under `root_id` with an explicit stack so deep trees can't overflow.
+    // Children are pushed in reverse, which keeps the order of a recursive pre-order walk.
+    fn collect_active_ids(
+        tasks: &impl TaskLookup,
+        root_id: usize,
+        visited: &mut HashSet<usize>,
+        active_ids: &mut Vec<usize>,
+    ) {
+        let is_completed = |id: usize| tasks.read(id, |fields, _, _| fields.completed);
+        let mut stack = vec![root_id];
+        let mut subtasks = Vec::new();
+        let mut predecessors = Vec::new();
+
+        while let Some(id) = stack.pop() {
+            // Shared subtasks can be reached through several parents, only collect them once
+            if !visited.insert(id) {
+                continue;
+            }
+            // Copied out so no task stays locked while its neighbours are looked up
+            let fields = tasks.read(id, |fields, task_subtasks, task_predecessors| {
+                subtasks.clear();
+                subtasks.extend_from_slice(task_subtasks);
+                predecessors.clear();
+                predecessors.extend(
+                    task_predecessors
+                        .iter()
+                        .filter(|dependency| dependency.hard)
+                        .map(|dependency| dependency.id),
+                );
+                fields
+            });
+            let fields = match fields {
+                Some(fields) => fields,
+                None => continue,
+            };
+
+            // A parent completed by hand may still have open children. It is never active
+            // itself, but we keep descending so those children don't drop out of view.
+            if fields.completed {
+                if subtasks.is_empty() {
+                    continue;
+                }
+            } else {
+                // A task waiting on an incomplete predecessor is blocked, along with its subtree
+                let blocked = predecessors
+                    .iter()
+                    .any(|&pid| is_completed(pid) == Some(false));
+                if blocked || fields.waiting {
+                    continue;
+                }
+            }
+
+            if subtasks.is_empty() {
+                active_ids.push(id);
+                continue;
+            }
+
+            let mut incomplete_subtasks = subtasks
+                .iter()
+                .copied()
+                .filter(|&sid| is_completed(sid) == Some(false));
+            // An ordered task only exposes its first incomplete subtask
+            let next: Vec<usize> = if fields.ordered {
+                incomplete_subtasks.next().into_iter().collect()
+            } else {
+                incomplete_subtasks.collect()
+            };
+
+            if next.is_empty() {
+                if !fields.completed {
+                    active_ids.push(id);
+                }
+            } else {
+                stack.extend(next.into_iter().rev());
+            }
         }
+    }
 
+    pub fn remove_task_recursive(&self, task_id: usize) -> Result<usize, String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&task_id) {
+            return Err(format!("Task with id: {} not found", task_id));
+        }
+        // Refuse before deleting anything, so a locked descendant never leaves a half-removed tree
+        if let Some(locked_id) = Self::subtree_ids(&tasks_map, task_id)
+            .into_iter()
+            .find(|id| tasks_map[id].locked)
         {
+            return Err(format!("Task with id: {} is locked", locked_id));
+        }
+        self.remove_subtree(task_id)
+    }
+
+    fn remove_subtree(&self, task_id: usize) -> Result<usize, String> {
+        let task_arc = {
+            let tasks = self.tasks.lock().unwrap();
+            tasks
+                .get(&task_id)
+                .ok_or(format!("Task with id: {} not found", task_id))?
+                .clone()
+        };
+
+        let parent_id = task_arc.lock().unwrap().parent;
+        let mut delete_count = 1;
+
+        loop {
+            let subtasks = task_arc.lock().unwrap().subtasks.clone();
+            for subtask_id in subtasks {
+                delete_count += self.remove_subtree(subtask_id)?;
+            }
+
             let mut tasks = self.tasks.lock().unwrap();
-            tasks.insert(id, subtask);
+            // A subtask added while the others were being removed would be orphaned, go round again
+            let has_new_subtasks = task_arc
+                .lock()
+                .unwrap()
+                .subtasks
+                .iter()
+                .any(|sid| tasks.contains_key(sid));
+            if has_new_subtasks {
+                continue;
+            }
+
+            tasks.remove(&task_id);
+            if let Some(parent) = parent_id.and_then(|pid| tasks.get(&pid)) {
+                parent
+                    .lock()
+                    .unwrap()
+                    .subtasks
+                    .retain(|&sid| sid != task_id);
+            }
+            for task in tasks.values() {
+                let mut task_lock = task.lock().unwrap();
+                task_lock
+                    .predecessors
+                    .retain(|dependency| dependency.id != task_id);
+            }
+            break;
+        }
+
+        {
+            let mut root_tasks = self.root_tasks.lock().unwrap();
+            if let Some(pos) = root_tasks.iter().position(|&id| id == task_id) {
+                root_tasks.remove(pos);
+            }
+        }
+
+        self.mark_dirty();
+        self.notify(TaskEvent::Removed(task_id));
+        Ok(delete_count)
+    }
+
+    pub fn get_subtasks_recursive(&self, id: usize, max_count: usize) -> Result<Vec<Task>, String> {
+        let task = {
+            let tasks = self.tasks.lock().unwrap();
+            tasks
+                .get(&id)
+                .ok_or(format!("Task with id: {} not found", id))?
+                .clone()
+        };
+
+        let subtasks_ids = {
+            let task_lock = task.lock().unwrap();
+            task_lock.subtasks.clone()
+        };
+
+        let tasks_map = {
+            let tasks = self.tasks.lock().unwrap();
+            tasks.clone()
+        };
+
+        let mut subtasks: Vec<Task> = Vec::new();
+        let mut subtasks_to_process = subtasks_ids;
+        let mut processed_count = 0;
+
+        while let Some(subtask_id) = subtasks_to_process.pop() {
+            if let Some(subtask) = tasks_map.get(&subtask_id) {
+                subtasks.push(subtask.lock().unwrap().clone());
+                processed_count += 1;
+
+                if processed_count >= max_count {
+                    break;
+                }
+
+                let subtask_lock = subtask.lock().unwrap();
+                subtasks_to_process.extend(subtask_lock.subtasks.iter().cloned());
+            }
+        }
+
+        Ok(subtasks)
+    }
+
+    pub fn get_subtasks(&self, id: usize) -> Result<Vec<Task>, String> {
+        let task = {
+            let tasks = self.tasks.lock().unwrap();
+            tasks
+                .get(&id)
+                .ok_or(format!("Task with id: {} not found", id))?
+                .clone()
+        };
+
+        let subtasks_ids = {
+            let task_lock = task.lock().unwrap();
+            task_lock.subtasks.clone()
+        };
+
+        let tasks_map = {
+            let tasks = self.tasks.lock().unwrap();
+            tasks.clone()
+        };
+
+        // A dangling id means the data is corrupted, report it instead of hiding it
+        subtasks_ids
+            .iter()
+            .map(|&sid| {
+                tasks_map
+                    .get(&sid)
+                    .map(|t| t.lock().unwrap().clone())
+                    .ok_or(format!("Subtask with id: {} of task {} not found", sid, id))
+            })
+            .collect()
+    }
+
+    pub fn get_parent_tasks(&self, task_id: usize) -> Result<Vec<Task>, String> {
+        let mut hierarchy = Vec::new();
+        let mut current_task_id = Some(task_id);
+
+        while let Some(id) = current_task_id {
+            let task = {
+                let tasks = self.tasks.lock().unwrap();
+                tasks
+                    .get(&id)
+                    .ok_or(format!("Task with id: {} not found", id))?
+                    .clone()
+            };
+
+            let task_lock = task.lock().unwrap();
+            hierarchy.push(task_lock.clone());
+
+            current_task_id = task_lock.parent;
+        }
+
+        Ok(hierarchy)
+    }
+
+    pub fn get_task(&self, id: usize) -> Option<Task> {
+        let tasks = self.tasks.lock().unwrap();
+        tasks.get(&id).map(|t| t.lock().unwrap().clone())
+    }
+
+    /// Returns the direct predecessors of a task, blocking ones first, then soft ones.
+    pub fn get_dependencies(&self, id: usize) -> Result<Vec<Task>, String> {
+        let tasks = self.tasks.lock().unwrap();
+        let predecessors: Vec<usize> = {
+            let task = tasks
+                .get(&id)
+                .ok_or(format!("Task with id: {} not found", id))?
+                .lock()
+                .unwrap();
+            task.hard_predecessors()
+                .chain(
+                    task.predecessors
+                        .iter()
+                        .filter(|dependency| !dependency.hard)
+                        .map(|dependency| dependency.id),
+                )
+                .collect()
+        };
+
+        Ok(predecessors
+            .iter()
+            .filter_map(|pid| tasks.get(pid))
+            .map(|t| t.lock().unwrap().clone())
+            .collect())
+    }
+
+    /// Returns the tasks that list `id` as a direct predecessor.
+    pub fn get_dependents(&self, id: usize) -> Result<Vec<Task>, String> {
+        let tasks = self.tasks.lock().unwrap();
+        if !tasks.contains_key(&id) {
+            return Err(format!("Task with id: {} not found", id));
         }
 
-        Ok(id)
+        let mut dependents: Vec<Task> = tasks
+            .values()
+            .map(|t| t.lock().unwrap())
+            .filter(|t| t.hard_predecessors().any(|pid| pid == id))
+            .map(|t| t.clone())
+            .collect();
+        dependents.sort_by_key(|t| t.id);
+        Ok(dependents)
+    }
+
+    /// Stores the shape and texts of a subtree under `name`, replacing any existing template.
+    pub fn save_as_template(&self, task_id: usize, name: String) -> Result<(), String> {
+        let tasks_map = self.snapshot();
+
+        let root = tasks_map
+            .get(&task_id)
+            .ok_or(format!("Task with id: {} not found", task_id))?;
+        let template = Self::build_template(root, &tasks_map);
+
+        self.templates.lock().unwrap().insert(name, template);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    fn build_template(task: &Task, tasks_map: &HashMap<usize, Task>) -> TemplateNode {
+        TemplateNode {
+            text: task.text.clone(),
+            ordered: task.ordered,
+            subtasks: task
+                .subtasks
+                .iter()
+                .filter_map(|sid| tasks_map.get(sid))
+                .map(|subtask| Self::build_template(subtask, tasks_map))
+                .collect(),
+        }
+    }
+
+    /// Creates a fresh, incomplete copy of a template under `parent`, or as a root task.
+    pub fn instantiate_template(&self, name: &str, parent: Option<usize>) -> Result<usize, String> {
+        let template = self
+            .templates
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or(format!("Template with name: {} not found", name))?;
+
+        self.check_task_limit(&self.tasks.lock().unwrap(), Self::template_size(&template))?;
+        self.instantiate_node(&template, parent)
+    }
+
+    fn template_size(node: &TemplateNode) -> usize {
+        1 + node.subtasks.iter().map(Self::template_size).sum::<usize>()
+    }
+
+    fn instantiate_node(
+        &self,
+        node: &TemplateNode,
+        parent: Option<usize>,
+    ) -> Result<usize, String> {
+        let id = match parent {
+            Some(parent_id) => {
+                let id = self.add_subtask(parent_id, node.text.clone())?;
+                let tasks = self.tasks.lock().unwrap();
+                if let Some(task) = tasks.get(&id) {
+                    task.lock().unwrap().ordered = node.ordered;
+                }
+                id
+            }
+            None => self.add_task(node.text.clone(), node.ordered)?,
+        };
+
+        for subtask in &node.subtasks {
+            self.instantiate_node(subtask, Some(id))?;
+        }
+
+        Ok(id)
+    }
+
+    /// Returns (id, text, completed) for each child, in subtask order.
+    pub fn get_subtask_labels(&self, id: usize) -> Result<Vec<(usize, String, bool)>, String> {
+        let tasks = self.tasks.lock().unwrap();
+        let subtasks_ids = tasks
+            .get(&id)
+            .ok_or(format!("Task with id: {} not found", id))?
+            .lock()
+            .unwrap()
+            .subtasks
+            .clone();
+
+        subtasks_ids
+            .iter()
+            .map(|&sid| {
+                let subtask = tasks
+                    .get(&sid)
+                    .ok_or(format!("Subtask with id: {} of task {} not found", sid, id))?
+                    .lock()
+                    .unwrap();
+                Ok((sid, subtask.text.clone(), subtask.completed))
+            })
+            .collect()
+    }
+
+    /// Freezes the current active set until `end_focus` is called.
+    pub fn begin_focus(&self) -> Vec<Task> {
+        let active_tasks = self.compute_active_tasks();
+        *self.focus.lock().unwrap() = Some(active_tasks.clone());
+        active_tasks
+    }
+
+    pub fn focus_set(&self) -> Option<Vec<Task>> {
+        self.focus.lock().unwrap().clone()
+    }
+
+    pub fn end_focus(&self) {
+        *self.focus.lock().unwrap() = None;
+    }
+
+    /// Creates tasks from an indented outline, two spaces or a tab per level.
+    /// Lines starting with `[x]` are created completed. Returns the top-level ids.
+    pub fn import_outline(&self, text: &str, parent: Option<usize>) -> Result<Vec<usize>, String> {
+        // Refuse up front rather than stopping halfway through the outline
+        let line_count = text.lines().filter(|line| !line.trim().is_empty()).count();
+        self.check_task_limit(&self.tasks.lock().unwrap(), line_count)?;
+
+        let mut created_roots = Vec::new();
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let mut level = 0;
+            let mut rest = line;
+            loop {
+                if let Some(stripped) = rest.strip_prefix('\t') {
+                    rest = stripped;
+                } else if let Some(stripped) = rest.strip_prefix("  ") {
+                    rest = stripped;
+                } else {
+                    break;
+                }
+                level += 1;
+            }
+
+            let rest = rest.trim();
+            let (completed, item_text) = if let Some(stripped) = rest.strip_prefix("[x]") {
+                (true, stripped.trim())
+            } else if let Some(stripped) = rest.strip_prefix("[ ]") {
+                (false, stripped.trim())
+            } else {
+                (false, rest)
+            };
+
+            while stack.last().is_some_and(|&(l, _)| l >= level) {
+                stack.pop();
+            }
+
+            let id = match stack.last().map(|&(_, id)| id).or(parent) {
+                Some(parent_id) => self.add_subtask(parent_id, item_text.to_string())?,
+                None => self.add_task(item_text.to_string(), true)?,
+            };
+            if stack.is_empty() {
+                created_roots.push(id);
+            }
+            if completed {
+                self.complete_task(id)?;
+            }
+
+            stack.push((level, id));
+        }
+
+        Ok(created_roots)
+    }
+
+    /// Detaches a subtask from its parent and makes it a root task, keeping its subtree.
+    pub fn promote_to_root(&self, id: usize) -> Result<(), String> {
+        let parent_id = {
+            let tasks = self.tasks.lock().unwrap();
+            let task = tasks
+                .get(&id)
+                .ok_or(format!("Task with id: {} not found", id))?;
+            let parent_id = task
+                .lock()
+                .unwrap()
+                .parent
+                .ok_or(format!("Task with id: {} is already a root task", id))?;
+            Self::check_unlocked(&tasks, id)?;
+            Self::check_unlocked(&tasks, parent_id)?;
+
+            if let Some(parent) = tasks.get(&parent_id) {
+                parent.lock().unwrap().subtasks.retain(|&sid| sid != id);
+            }
+            task.lock().unwrap().parent = None;
+            parent_id
+        };
+
+        self.root_tasks.lock().unwrap().push(id);
+
+        self.upsert_task(id)?;
+        self.notify(TaskEvent::Reordered(parent_id));
+        Ok(())
+    }
+
+    /// Marks a task as waiting on someone else, `None` clears it.
+    pub fn set_waiting(&self, id: usize, note: Option<String>) -> Result<(), String> {
+        self.with_unlocked_task(id, |task| task.waiting = note)
+    }
+
+    pub fn get_waiting_tasks(&self) -> Vec<Task> {
+        let mut waiting_tasks: Vec<Task> = self
+            .snapshot()
+            .into_values()
+            .filter(|t| !t.completed && t.waiting.is_some())
+            .collect();
+        waiting_tasks.sort_by_key(|t| t.id);
+        waiting_tasks
+    }
+
+    /// Completed tasks, most recently completed first.
+    pub fn get_recently_completed(&self, limit: usize) -> Vec<Task> {
+        let mut completed: Vec<Task> = self
+            .snapshot()
+            .into_values()
+            .filter(|t| t.completed)
+            .collect();
+        completed.sort_by_key(|t| std::cmp::Reverse((t.completed_at, t.id)));
+        completed.truncate(limit);
+        completed
+    }
+
+    /// Applies several reorders at once, either all of them or none.
+    pub fn apply_reorders(&self, reorders: Vec<(usize, Vec<usize>)>) -> Result<(), String> {
+        let tasks_map = self.tasks.lock().unwrap();
+
+        let mut parents = Vec::new();
+        for (parent_id, new_order) in &reorders {
+            let parent_task_arc = tasks_map
+                .get(parent_id)
+                .ok_or(format!("Parent task with id: {} not found", parent_id))?
+                .clone();
+
+            {
+                let parent_task_lock = parent_task_arc.lock().unwrap();
+                if parent_task_lock.locked {
+                    return Err(format!("Task with id: {} is locked", parent_id));
+                }
+                let current_subtasks_set: HashSet<_> =
+                    parent_task_lock.subtasks.iter().cloned().collect();
+                let new_subtasks_set: HashSet<_> = new_order.iter().cloned().collect();
+                if current_subtasks_set != new_subtasks_set
+                    || parent_task_lock.subtasks.len() != new_order.len()
+                {
+                    return Err(format!(
+                        "New order for task {} must contain the same subtasks",
+                        parent_id
+                    ));
+                }
+            }
+
+            parents.push(parent_task_arc);
+        }
+
+        for (parent_task_arc, (_, new_order)) in parents.iter().zip(&reorders) {
+            parent_task_arc.lock().unwrap().subtasks = new_order.clone();
+        }
+        drop(tasks_map);
+
+        for (parent_id, _) in &reorders {
+            self.upsert_task(*parent_id)?;
+            self.notify(TaskEvent::Reordered(*parent_id));
+        }
+        Ok(())
+    }
+
+    /// Looks up several tasks at once, `None` for ids that don't exist.
+    pub fn get_tasks(&self, ids: Vec<usize>) -> Vec<Option<Task>> {
+        let tasks = self.tasks.lock().unwrap();
+        ids.iter()
+            .map(|id| tasks.get(id).map(|t| t.lock().unwrap().clone()))
+            .collect()
+    }
+
+    /// One active task per line, prefixed with its root project, e.g. `[Project] Task`.
+    pub fn export_active_plaintext(&self) -> String {
+        let tasks_map = self.snapshot();
+
+        self.get_active_tasks()
+            .iter()
+            .map(|task| {
+                let mut root = task;
+                let mut visited = HashSet::new();
+                while let Some(parent) = root.parent.and_then(|pid| tasks_map.get(&pid)) {
+                    if !visited.insert(parent.id) {
+                        break;
+                    }
+                    root = parent;
+                }
+                format!("[{}] {}", root.text, task.text)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn with_task_mut<R>(&self, id: usize, f: impl FnOnce(&mut Task) -> R) -> Result<R, String> {
+        let result = {
+            let tasks = self.tasks.lock().unwrap();
+            let task = tasks
+                .get(&id)
+                .ok_or(format!("Task with id: {} not found", id))?;
+            let mut task_lock = task.lock().unwrap();
+            f(&mut task_lock)
+        };
+        self.upsert_task(id)?;
+        Ok(result)
+    }
+
+    // Like `with_task_mut`, but refuses a locked task, checked under the same guard as the edit
+    fn with_unlocked_task<R>(
+        &self,
+        id: usize,
+        f: impl FnOnce(&mut Task) -> R,
+    ) -> Result<R, String> {
+        let result = {
+            let tasks = self.tasks.lock().unwrap();
+            let task = tasks
+                .get(&id)
+                .ok_or(format!("Task with id: {} not found", id))?;
+            let mut task_lock = task.lock().unwrap();
+            if task_lock.locked {
+                return Err(format!("Task with id: {} is locked", id));
+            }
+            f(&mut task_lock)
+        };
+        self.upsert_task(id)?;
+        Ok(result)
+    }
+
+    pub fn set_priority(&self, id: usize, priority: u8) -> Result<(), String> {
+        self.with_task_mut(id, |task| task.priority = priority)
+    }
+
+    pub fn set_due_date(&self, id: usize, due_date: Option<i64>) -> Result<(), String> {
+        self.with_task_mut(id, |task| task.due_date = due_date)
+    }
+
+    pub fn add_tag(&self, id: usize, tag: String) -> Result<(), String> {
+        self.with_task_mut(id, |task| {
+            if !task.tags.contains(&tag) {
+                task.tags.push(tag);
+            }
+        })
+    }
+
+    pub fn remove_tag(&self, id: usize, tag: &str) -> Result<(), String> {
+        self.with_task_mut(id, |task| task.tags.retain(|t| t != tag))
+    }
+
+    pub fn filter_tasks(&self, filter: TaskFilter) -> Vec<Task> {
+        let mut tasks: Vec<Task> = self
+            .snapshot()
+            .into_values()
+            .filter(|t| filter.matches(t))
+            .collect();
+        tasks.sort_by_key(|t| t.id);
+        tasks
+    }
+
+    /// Renames a tag on every task carrying it, returns how many tasks changed.
+    pub fn rename_tag(&self, old: &str, new: &str) -> usize {
+        // Renaming onto itself would take the merge path below and drop the tag
+        if old == new {
+            return 0;
+        }
+        let tasks = self.tasks.lock().unwrap();
+        let mut updated = 0;
+
+        for task in tasks.values() {
+            let mut task_lock = task.lock().unwrap();
+            if !task_lock.tags.iter().any(|t| t == old) {
+                continue;
+            }
+
+            if task_lock.tags.iter().any(|t| t == new) {
+                task_lock.tags.retain(|t| t != old);
+            } else {
+                for tag in task_lock.tags.iter_mut().filter(|t| *t == old) {
+                    *tag = new.to_string();
+                }
+            }
+            updated += 1;
+        }
+        drop(tasks);
+
+        if updated > 0 {
+            self.mark_dirty();
+        }
+        updated
+    }
+
+    /// A page of the active set in its usual tree order, plus the total active count.
+    pub fn get_active_tasks_page(&self, offset: usize, limit: usize) -> (Vec<Task>, usize) {
+        let active_tasks = self.get_active_tasks();
+        let total = active_tasks.len();
+        let page = active_tasks.into_iter().skip(offset).take(limit).collect();
+        (page, total)
+    }
+
+    /// Nodes and typed edges for a graph view. Edges point from the task that must come first.
+    pub fn export_graph(&self) -> GraphData {
+        let tasks_map = self.snapshot();
+        let mut ids: Vec<usize> = tasks_map.keys().cloned().collect();
+        ids.sort();
+
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+
+        for id in ids {
+            let task = &tasks_map[&id];
+            nodes.push((task.id, task.text.clone(), task.completed));
+
+            for predecessor in task.hard_predecessors() {
+                if tasks_map.contains_key(&predecessor) {
+                    edges.push(GraphEdge {
+                        from: predecessor,
+                        to: task.id,
+                        kind: EdgeKind::Predecessor,
+                    });
+                }
+            }
+
+            let subtasks: Vec<usize> = task
+                .subtasks
+                .iter()
+                .cloned()
+                .filter(|sid| tasks_map.contains_key(sid))
+                .collect();
+            for &subtask_id in &subtasks {
+                edges.push(GraphEdge {
+                    from: task.id,
+                    to: subtask_id,
+                    kind: EdgeKind::Parent,
+                });
+            }
+            if task.ordered {
+                for pair in subtasks.windows(2) {
+                    edges.push(GraphEdge {
+                        from: pair[0],
+                        to: pair[1],
+                        kind: EdgeKind::Ordered,
+                    });
+                }
+            }
+        }
+
+        GraphData { nodes, edges }
+    }
+
+    // Pre-order ids of a task and its descendants, guarded against shared or cyclic links
+    fn subtree_ids(tasks_map: &HashMap<usize, Task>, root_id: usize) -> Vec<usize> {
+        let mut ids = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![root_id];
+
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            if let Some(task) = tasks_map.get(&id) {
+                ids.push(id);
+                stack.extend(task.subtasks.iter().rev());
+            }
+        }
+
+        ids
+    }
+
+    /// Serializes one task and its descendants as a standalone save file, ids preserved.
+    pub fn export_subtree_json(&self, root_id: usize) -> Result<String, String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&root_id) {
+            return Err(format!("Task with id: {} not found", root_id));
+        }
+
+        let ids = Self::subtree_ids(&tasks_map, root_id);
+        let id_set: HashSet<usize> = ids.iter().copied().collect();
+        let tasks: Vec<TaskRecord> = ids
+            .iter()
+            .map(|id| {
+                let mut task = tasks_map[id].clone();
+                if task.id == root_id {
+                    task.parent = None;
+                }
+                // Dependencies outside the subtree would dangle in the exported file
+                task.predecessors
+                    .retain(|dependency| id_set.contains(&dependency.id));
+                task.into()
+            })
+            .collect();
+
+        let data = TaskManagerData {
+            version: DATA_VERSION,
+            next_id: ids.iter().max().map_or(1, |max| max + 1),
+            tasks,
+            root_tasks: vec![root_id],
+            templates: BTreeMap::new(),
+        };
+
+        serde_json::to_string(&data).map_err(|e| format!("Failed to serialize subtree: {}", e))
+    }
+
+    /// Ids from the task itself up to its root, like `get_parent_tasks` without the clones.
+    pub fn get_ancestor_ids(&self, id: usize) -> Result<Vec<usize>, String> {
+        let tasks = self.tasks.lock().unwrap();
+        let mut ancestors = Vec::new();
+        let mut current_task_id = Some(id);
+
+        while let Some(current_id) = current_task_id {
+            if ancestors.contains(&current_id) {
+                return Err(format!("Parent cycle detected at task {}", current_id));
+            }
+            let task = tasks
+                .get(&current_id)
+                .ok_or(format!("Task with id: {} not found", current_id))?;
+            ancestors.push(current_id);
+            current_task_id = task.lock().unwrap().parent;
+        }
+
+        Ok(ancestors)
+    }
+
+    /// Moves one task within its sibling list, `parent_id` of `None` means the root list.
+    pub fn reorder_move(
+        &self,
+        parent_id: Option<usize>,
+        task_id: usize,
+        to_index: usize,
+    ) -> Result<(), String> {
+        let move_within = |siblings: &mut Vec<usize>| -> Result<(), String> {
+            let from_index = siblings
+                .iter()
+                .position(|&id| id == task_id)
+                .ok_or(format!("Task with id: {} is not in this list", task_id))?;
+            siblings.remove(from_index);
+            siblings.insert(to_index.min(siblings.len()), task_id);
+            Ok(())
+        };
+
+        match parent_id {
+            Some(parent_id) => {
+                {
+                    let tasks = self.tasks.lock().unwrap();
+                    let parent = tasks
+                        .get(&parent_id)
+                        .ok_or(format!("Parent task with id: {} not found", parent_id))?;
+                    Self::check_unlocked(&tasks, parent_id)?;
+                    move_within(&mut parent.lock().unwrap().subtasks)?;
+                }
+                self.upsert_task(parent_id)?;
+                self.notify(TaskEvent::Reordered(parent_id));
+            }
+            None => {
+                move_within(&mut self.root_tasks.lock().unwrap())?;
+                self.mark_dirty();
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn add_attachment(&self, id: usize, path: String) -> Result<(), String> {
+        let path = path.trim().to_string();
+        if path.is_empty() {
+            return Err("Attachment path must not be empty".to_string());
+        }
+        self.with_task_mut(id, |task| {
+            if !task.attachments.contains(&path) {
+                task.attachments.push(path);
+            }
+        })
+    }
+
+    pub fn remove_attachment(&self, id: usize, path: &str) -> Result<(), String> {
+        self.with_task_mut(id, |task| task.attachments.retain(|a| a != path))
+    }
+
+    // A task is done when completed itself, or when all of its subtasks are done
+    fn is_done(
+        task: &Task,
+        tasks_map: &HashMap<usize, Task>,
+        visited: &mut HashSet<usize>,
+    ) -> bool {
+        if task.completed {
+            return true;
+        }
+        if task.subtasks.is_empty() || !visited.insert(task.id) {
+            return false;
+        }
+        task.subtasks
+            .iter()
+            .filter_map(|sid| tasks_map.get(sid))
+            .all(|subtask| Self::is_done(subtask, tasks_map, visited))
+    }
+
+    /// Returns the root id if completing `id` would finish its whole project.
+    pub fn would_complete_project(&self, id: usize) -> Result<Option<usize>, String> {
+        let mut tasks_map = self.snapshot();
+        let mut root_id = id;
+        let mut visited = HashSet::new();
+        while let Some(parent_id) = tasks_map
+            .get(&root_id)
+            .ok_or(format!("Task with id: {} not found", root_id))?
+            .parent
+        {
+            if !visited.insert(parent_id) {
+                return Err(format!("Parent cycle detected at task {}", parent_id));
+            }
+            root_id = parent_id;
+        }
+
+        if Self::is_done(&tasks_map[&root_id], &tasks_map, &mut HashSet::new()) {
+            return Ok(None);
+        }
+
+        if let Some(task) = tasks_map.get_mut(&id) {
+            task.completed = true;
+        }
+        let done = Self::is_done(&tasks_map[&root_id], &tasks_map, &mut HashSet::new());
+        Ok(done.then_some(root_id))
+    }
+
+    /// Adds a task from text with inline `!priority`, `#tag` and `@YYYY-MM-DD` markers.
+    pub fn quick_add(&self, input: &str, parent: Option<usize>) -> Result<usize, String> {
+        let mut words = Vec::new();
+        let mut priority = None;
+        let mut tags: Vec<String> = Vec::new();
+        let mut due_date = None;
+
+        for word in input.split_whitespace() {
+            if let Some(value) = word.strip_prefix('!').and_then(|p| p.parse::<u8>().ok()) {
+                priority = Some(value);
+            } else if let Some(tag) = word.strip_prefix('#').filter(|t| !t.is_empty()) {
+                if !tags.iter().any(|t| t == tag) {
+                    tags.push(tag.to_string());
+                }
+            } else if let Some(date) = word.strip_prefix('@').and_then(parse_date) {
+                due_date = Some(date);
+            } else {
+                words.push(word);
+            }
+        }
+
+        let text = words.join(" ");
+        if text.is_empty() {
+            return Err("Task text must not be empty".to_string());
+        }
+
+        let id = match parent {
+            Some(parent_id) => self.add_subtask(parent_id, text)?,
+            None => self.add_task(text, true)?,
+        };
+        self.with_task_mut(id, |task| {
+            task.priority = priority.unwrap_or(task.priority);
+            task.tags = tags;
+            task.due_date = due_date;
+        })?;
+
+        Ok(id)
+    }
+
+    /// Active tasks due in `[from, to)`, grouped by UTC day and sorted by due date.
+    pub fn get_agenda(&self, from: i64, to: i64) -> Vec<(i64, Vec<Task>)> {
+        let mut days: BTreeMap<i64, Vec<Task>> = BTreeMap::new();
+
+        for task in self.compute_active_tasks() {
+            if let Some(due) = task.due_date.filter(|due| (from..to).contains(due)) {
+                let day = due - due.rem_euclid(86_400);
+                days.entry(day).or_default().push(task);
+            }
+        }
+
+        days.into_iter()
+            .map(|(day, mut tasks)| {
+                tasks.sort_by_key(|t| (t.due_date, t.id));
+                (day, tasks)
+            })
+            .collect()
+    }
+
+    // The list a task lives in, including the task itself: its parent's subtasks or the roots
+    fn sibling_ids(&self, id: usize) -> Result<Vec<usize>, String> {
+        let tasks = self.tasks.lock().unwrap();
+        let task = tasks
+            .get(&id)
+            .ok_or(format!("Task with id: {} not found", id))?;
+        let parent_id = task.lock().unwrap().parent;
+
+        match parent_id {
+            Some(parent_id) => {
+                let parent = tasks
+                    .get(&parent_id)
+                    .ok_or(format!("Parent task with id: {} not found", parent_id))?;
+                let subtasks = parent.lock().unwrap().subtasks.clone();
+                Ok(subtasks)
+            }
+            None => Ok(self.root_tasks.lock().unwrap().clone()),
+        }
+    }
+
+    /// The other tasks sharing this task's parent (or the other roots), in order.
+    pub fn get_siblings(&self, id: usize) -> Result<Vec<Task>, String> {
+        let sibling_ids = self.sibling_ids(id)?;
+        let tasks = self.tasks.lock().unwrap();
+        Ok(sibling_ids
+            .iter()
+            .filter(|&&sid| sid != id)
+            .filter_map(|sid| tasks.get(sid))
+            .map(|t| t.lock().unwrap().clone())
+            .collect())
+    }
+
+    pub fn start_task(&self, id: usize) -> Result<(), String> {
+        self.start_task_at(id, current_timestamp())
+    }
+
+    pub fn start_task_at(&self, id: usize, timestamp: i64) -> Result<(), String> {
+        self.with_task_mut(id, |task| {
+            if task.completed {
+                return Err(format!("Task with id: {} is already completed", id));
+            }
+            task.in_progress = true;
+            task.started_at.get_or_insert(timestamp);
+            Ok(())
+        })?
+    }
+
+    pub fn stop_task(&self, id: usize) -> Result<(), String> {
+        self.stop_task_at(id, current_timestamp())
+    }
+
+    pub fn stop_task_at(&self, id: usize, timestamp: i64) -> Result<(), String> {
+        self.with_task_mut(id, |task| task.stop_timer(timestamp))
+    }
+
+    pub fn get_in_progress_tasks(&self) -> Vec<Task> {
+        let mut tasks: Vec<Task> = self
+            .snapshot()
+            .into_values()
+            .filter(|t| t.in_progress && !t.completed)
+            .collect();
+        tasks.sort_by_key(|t| t.id);
+        tasks
+    }
+
+    /// Counts tasks per lifecycle bucket: todo, in progress and completed.
+    pub fn get_stats(&self) -> TaskStats {
+        let tasks_map = self.snapshot();
+        let mut stats = TaskStats {
+            total: tasks_map.len(),
+            active: self.compute_active_tasks().len(),
+            ..Default::default()
+        };
+
+        for task in tasks_map.values() {
+            if task.completed {
+                stats.completed += 1;
+            } else if task.in_progress {
+                stats.in_progress += 1;
+            } else {
+                stats.todo += 1;
+            }
+        }
+
+        stats
+    }
+
+    /// Tasks that no parent lists as a subtask and that are not root tasks either.
+    pub fn get_orphans(&self) -> Vec<Task> {
+        let tasks_map = self.snapshot();
+        let root_tasks: HashSet<usize> = self.root_tasks.lock().unwrap().iter().cloned().collect();
+        let referenced: HashSet<usize> = tasks_map
+            .values()
+            .flat_map(|t| t.subtasks.iter().cloned())
+            .collect();
+
+        let mut orphans: Vec<Task> = tasks_map
+            .into_values()
+            .filter(|t| !referenced.contains(&t.id) && !root_tasks.contains(&t.id))
+            .collect();
+        orphans.sort_by_key(|t| t.id);
+        orphans
+    }
+
+    /// Longest chain of predecessor and ordered-sibling links within a task's subtree.
+    pub fn critical_path(&self, root_id: usize) -> Result<Vec<usize>, String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&root_id) {
+            return Err(format!("Task with id: {} not found", root_id));
+        }
+
+        let ids = Self::subtree_ids(&tasks_map, root_id);
+        let in_subtree: HashSet<usize> = ids.iter().cloned().collect();
+
+        let mut edges = Vec::new();
+        for &id in &ids {
+            let task = &tasks_map[&id];
+            for predecessor in task.hard_predecessors() {
+                if in_subtree.contains(&predecessor) {
+                    edges.push((predecessor, id));
+                }
+            }
+            if task.ordered {
+                let subtasks: Vec<usize> = task
+                    .subtasks
+                    .iter()
+                    .cloned()
+                    .filter(|sid| in_subtree.contains(sid))
+                    .collect();
+                for pair in subtasks.windows(2) {
+                    edges.push((pair[0], pair[1]));
+                }
+            }
+        }
+
+        let mut successors: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut in_degree: HashMap<usize, usize> = ids.iter().map(|&id| (id, 0)).collect();
+        for &(from, to) in &edges {
+            successors.entry(from).or_default().push(to);
+            *in_degree.get_mut(&to).unwrap() += 1;
+        }
+
+        // Topological order; a task's chain length is final once it is dequeued
+        let mut queue: VecDeque<usize> = ids
+            .iter()
+            .cloned()
+            .filter(|id| in_degree[id] == 0)
+            .collect();
+        let mut length: HashMap<usize, usize> = ids.iter().map(|&id| (id, 1)).collect();
+        let mut previous: HashMap<usize, usize> = HashMap::new();
+        let mut best = root_id;
+        let mut processed = 0;
+
+        while let Some(id) = queue.pop_front() {
+            processed += 1;
+            if length[&id] > length[&best] {
+                best = id;
+            }
+            for &next in successors.get(&id).into_iter().flatten() {
+                if length[&id] + 1 > length[&next] {
+                    length.insert(next, length[&id] + 1);
+                    previous.insert(next, id);
+                }
+                let degree = in_degree.get_mut(&next).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        if processed < ids.len() {
+            return Err(format!("Dependency cycle detected under task {}", root_id));
+        }
+
+        let mut path = vec![best];
+        while let Some(&prev) = previous.get(path.last().unwrap()) {
+            path.push(prev);
+        }
+        path.reverse();
+        Ok(path)
+    }
+
+    pub fn set_recurrence(&self, id: usize, recurrence: Option<Recurrence>) -> Result<(), String> {
+        if recurrence.is_some_and(|r| r.interval_days() == 0) {
+            return Err("Recurrence interval must be at least one day".to_string());
+        }
+        self.with_task_mut(id, |task| task.recurrence = recurrence)
+    }
+
+    /// The next `count` due dates of a recurring task, without creating anything.
+    pub fn preview_occurrences(&self, id: usize, count: usize) -> Result<Vec<i64>, String> {
+        let task = self
+            .get_task(id)
+            .ok_or(format!("Task with id: {} not found", id))?;
+        let recurrence = task
+            .recurrence
+            .ok_or(format!("Task with id: {} does not recur", id))?;
+        let due_date = task
+            .due_date
+            .ok_or(format!("Task with id: {} has no due date", id))?;
+
+        let interval = i64::from(recurrence.interval_days()) * 86_400;
+        Ok((1..=count as i64)
+            .map(|n| due_date + n * interval)
+            .collect())
+    }
+
+    /// Groups of tasks whose predecessors block each other, including self-dependencies.
+    pub fn find_cycles(&self) -> Vec<Vec<usize>> {
+        let tasks_map = self.snapshot();
+        let mut ids: Vec<usize> = tasks_map.keys().cloned().collect();
+        ids.sort();
+
+        let mut edges: HashMap<usize, Vec<usize>> = HashMap::new();
+        for &id in &ids {
+            for predecessor in tasks_map[&id].hard_predecessors() {
+                if tasks_map.contains_key(&predecessor) {
+                    edges.entry(predecessor).or_default().push(id);
+                }
+            }
+        }
+
+        let mut finder = CycleFinder {
+            edges: &edges,
+            next_index: 0,
+            index: HashMap::new(),
+            lowlink: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            components: Vec::new(),
+        };
+        for &id in &ids {
+            if !finder.index.contains_key(&id) {
+                finder.visit(id);
+            }
+        }
+
+        let mut cycles: Vec<Vec<usize>> = finder
+            .components
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1
+                    || tasks_map[&component[0]]
+                        .hard_predecessors()
+                        .any(|pid| pid == component[0])
+            })
+            .map(|mut component| {
+                component.sort();
+                component
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
+
+    /// Removes several subtrees at once, returning every id that was deleted.
+    /// Ids inside another selected subtree go with it.
+    pub fn remove_tasks(&self, ids: Vec<usize>) -> Result<Vec<usize>, String> {
+        let tasks_map = self.snapshot();
+        if let Some(missing) = ids.iter().find(|id| !tasks_map.contains_key(id)) {
+            return Err(format!("Task with id: {} not found", missing));
+        }
+
+        // Only the top-most selected tasks are removed, their subtrees take the rest
+        let selected: HashSet<usize> = ids.iter().copied().collect();
+        let mut top_most = Vec::new();
+        let mut removed = Vec::new();
+        for &id in &ids {
+            let mut seen = HashSet::from([id]);
+            let mut ancestor = tasks_map[&id].parent;
+            let mut nested = false;
+            while let Some(ancestor_id) = ancestor.filter(|aid| seen.insert(*aid)) {
+                if selected.contains(&ancestor_id) {
+                    nested = true;
+                    break;
+                }
+                ancestor = tasks_map.get(&ancestor_id).and_then(|task| task.parent);
+            }
+            if !nested && !top_most.contains(&id) {
+                top_most.push(id);
+                removed.extend(Self::subtree_ids(&tasks_map, id));
+            }
+        }
+
+        if let Some(locked_id) = removed.iter().find(|id| tasks_map[id].locked) {
+            return Err(format!("Task with id: {} is locked", locked_id));
+        }
+        for id in top_most {
+            self.remove_subtree(id)?;
+        }
+        Ok(removed)
+    }
+
+    /// Subtasks in the requested order; ties keep their manual order.
+    pub fn get_subtasks_sorted(&self, id: usize, sort: SubtaskSort) -> Result<Vec<Task>, String> {
+        // The manual order comes first, so the stable sorts below break ties by it.
+        // Tasks without a sort key keep their position as the key.
+        let mut keyed: Vec<(f64, Task)> = self
+            .get_subtasks(id)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, task)| (task.sort_key.unwrap_or(i as f64), task))
+            .collect();
+        keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut subtasks: Vec<Task> = keyed.into_iter().map(|(_, task)| task).collect();
+
+        match sort {
+            SubtaskSort::Manual => {}
+            SubtaskSort::IncompleteFirst => subtasks.sort_by_key(|task| task.completed),
+            SubtaskSort::DueDate => {
+                subtasks.sort_by_key(|task| (task.due_date.is_none(), task.due_date))
+            }
+            SubtaskSort::Priority => subtasks.sort_by_key(|task| std::cmp::Reverse(task.priority)),
+        }
+        Ok(subtasks)
+    }
+
+    /// Warns where a proposed order puts a task ahead of a sibling it depends on.
+    pub fn validate_reorder(
+        &self,
+        parent_id: usize,
+        new_order: Vec<usize>,
+    ) -> Result<Vec<String>, String> {
+        let tasks_map = self.snapshot();
+        let parent = tasks_map
+            .get(&parent_id)
+            .ok_or(format!("Parent task with id: {} not found", parent_id))?;
+
+        let current_subtasks_set: HashSet<_> = parent.subtasks.iter().cloned().collect();
+        let new_subtasks_set: HashSet<_> = new_order.iter().cloned().collect();
+        if current_subtasks_set != new_subtasks_set {
+            return Err("New order must contain the same subtasks".to_string());
+        }
+        if !parent.ordered {
+            return Ok(Vec::new());
+        }
+
+        let position: HashMap<usize, usize> = new_order
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| (id, index))
+            .collect();
+        let mut warnings = Vec::new();
+        for (index, id) in new_order.iter().enumerate() {
+            let predecessors = tasks_map
+                .get(id)
+                .into_iter()
+                .flat_map(Task::hard_predecessors);
+            for predecessor in predecessors {
+                if position.get(&predecessor).is_some_and(|&p| p > index) {
+                    warnings.push(format!(
+                        "Task {} would come before its predecessor {}",
+                        id, predecessor
+                    ));
+                }
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Open tasks in each root's subtree, the root included, for "n left" badges.
+    pub fn incomplete_counts_by_root(&self) -> HashMap<usize, usize> {
+        let tasks_map = self.snapshot();
+        let root_tasks = self.root_tasks.lock().unwrap().clone();
+
+        root_tasks
+            .into_iter()
+            .filter_map(|root_id| {
+                let root_open = usize::from(!tasks_map.get(&root_id)?.completed);
+                let count = root_open + Self::incomplete_descendants(&tasks_map, root_id);
+                Some((root_id, count))
+            })
+            .collect()
+    }
+
+    fn incomplete_descendants(tasks_map: &HashMap<usize, Task>, root_id: usize) -> usize {
+        Self::subtree_ids(tasks_map, root_id)
+            .into_iter()
+            .filter(|id| *id != root_id && !tasks_map[id].completed)
+            .count()
+    }
+
+    /// Deletes a task but moves its children up into its place first.
+    pub fn delete_keep_children(&self, id: usize) -> Result<(), String> {
+        let (parent_id, children) = {
+            let tasks = self.tasks.lock().unwrap();
+            let task = tasks
+                .get(&id)
+                .ok_or(format!("Task with id: {} not found", id))?;
+            Self::check_unlocked(&tasks, id)?;
+            let (parent_id, subtasks) = {
+                let mut task_lock = task.lock().unwrap();
+                (task_lock.parent, std::mem::take(&mut task_lock.subtasks))
+            };
+            let children: Vec<usize> = subtasks
+                .into_iter()
+                .filter(|sid| tasks.contains_key(sid))
+                .collect();
+            for child_id in &children {
+                tasks[child_id].lock().unwrap().parent = parent_id;
+            }
+
+            let put_in_place =
+                |siblings: &mut Vec<usize>| match siblings.iter().position(|&sid| sid == id) {
+                    Some(pos) => {
+                        siblings.splice(pos..=pos, children.iter().cloned());
+                    }
+                    None => siblings.extend(children.iter().cloned()),
+                };
+            match parent_id.and_then(|pid| tasks.get(&pid)) {
+                Some(parent) => put_in_place(&mut parent.lock().unwrap().subtasks),
+                None => put_in_place(&mut self.root_tasks.lock().unwrap()),
+            }
+            (parent_id, children)
+        };
+
+        self.remove_task_recursive(id)?;
+        for child_id in children {
+            self.upsert_task(child_id)?;
+        }
+        if let Some(parent_id) = parent_id {
+            self.notify(TaskEvent::Reordered(parent_id));
+        }
+        Ok(())
+    }
+
+    /// Locked tasks reject edits, completion, removal and reordering of their children.
+    pub fn lock_task(&self, id: usize) -> Result<(), String> {
+        self.with_task_mut(id, |task| task.locked = true)
+    }
+
+    pub fn unlock_task(&self, id: usize) -> Result<(), String> {
+        self.with_task_mut(id, |task| task.locked = false)
+    }
+
+    pub fn estimate_finish_date(&self, root_id: usize, tasks_per_day: f32) -> Result<i64, String> {
+        self.estimate_finish_date_from(root_id, tasks_per_day, current_timestamp())
+    }
+
+    /// Projects when a root's open descendants are done at a steady daily rate.
+    pub fn estimate_finish_date_from(
+        &self,
+        root_id: usize,
+        tasks_per_day: f32,
+        now: i64,
+    ) -> Result<i64, String> {
+        if !tasks_per_day.is_finite() || tasks_per_day <= 0.0 {
+            return Err("Throughput must be a positive number of tasks per day".to_string());
+        }
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&root_id) {
+            return Err(format!("Task with id: {} not found", root_id));
+        }
+
+        let remaining = Self::incomplete_descendants(&tasks_map, root_id);
+        let seconds = (remaining as f64 / f64::from(tasks_per_day) * 86_400.0).ceil();
+        Ok(now + seconds as i64)
+    }
+
+    /// Exchanges the places of two tasks, even across different parents.
+    pub fn swap_tasks(&self, a: usize, b: usize) -> Result<(), String> {
+        if a == b {
+            return Ok(());
+        }
+        let (ancestors_a, ancestors_b) = (self.get_ancestor_ids(a)?, self.get_ancestor_ids(b)?);
+        if ancestors_a.contains(&b) || ancestors_b.contains(&a) {
+            return Err(format!(
+                "Cannot swap task {} with its own ancestor or descendant {}",
+                a, b
+            ));
+        }
+
+        let index_of = |id: usize| -> Result<usize, String> {
+            self.sibling_ids(id)?
+                .iter()
+                .position(|&sid| sid == id)
+                .ok_or(format!("Task with id: {} is not in its parent's list", id))
+        };
+        let (index_a, index_b) = (index_of(a)?, index_of(b)?);
+
+        let (parent_a, parent_b) = {
+            let tasks = self.tasks.lock().unwrap();
+            let parent_a = tasks[&a].lock().unwrap().parent;
+            let parent_b = tasks[&b].lock().unwrap().parent;
+            // Both tasks move, and both parents get their subtask lists rewritten
+            for id in [a, b].into_iter().chain(parent_a).chain(parent_b) {
+                Self::check_unlocked(&tasks, id)?;
+            }
+
+            for (parent, index, id) in [(parent_a, index_a, b), (parent_b, index_b, a)] {
+                match parent.and_then(|pid| tasks.get(&pid)) {
+                    Some(parent) => parent.lock().unwrap().subtasks[index] = id,
+                    None => self.root_tasks.lock().unwrap()[index] = id,
+                }
+            }
+            tasks[&a].lock().unwrap().parent = parent_b;
+            tasks[&b].lock().unwrap().parent = parent_a;
+            (parent_a, parent_b)
+        };
+
+        self.upsert_task(a)?;
+        self.upsert_task(b)?;
+        let mut reordered: Vec<usize> = parent_a.into_iter().chain(parent_b).collect();
+        reordered.dedup();
+        for parent_id in reordered {
+            self.notify(TaskEvent::Reordered(parent_id));
+        }
+        Ok(())
+    }
+
+    // Own tags first, then each ancestor's from the nearest up, without repeats
+    fn effective_tags(tasks_map: &HashMap<usize, Task>, id: usize) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        let mut visited = HashSet::new();
+        let mut current_id = Some(id);
+
+        while let Some(task) = current_id
+            .filter(|cid| visited.insert(*cid))
+            .and_then(|cid| tasks_map.get(&cid))
+        {
+            for tag in &task.tags {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+            current_id = task.parent;
+        }
+        tags
+    }
+
+    /// A task's tags together with everything inherited from its ancestors.
+    pub fn get_effective_tags(&self, id: usize) -> Result<Vec<String>, String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&id) {
+            return Err(format!("Task with id: {} not found", id));
+        }
+        Ok(Self::effective_tags(&tasks_map, id))
+    }
+
+    /// Tasks carrying `tag`, optionally counting tags inherited from ancestors.
+    pub fn get_tasks_by_tag(&self, tag: &str, include_inherited: bool) -> Vec<Task> {
+        let tasks_map = self.snapshot();
+        let mut tasks: Vec<Task> = tasks_map
+            .values()
+            .filter(|task| {
+                if include_inherited {
+                    Self::effective_tags(&tasks_map, task.id)
+                        .iter()
+                        .any(|t| t == tag)
+                } else {
+                    task.tags.iter().any(|t| t == tag)
+                }
+            })
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|t| t.id);
+        tasks
+    }
+
+    /// The active leaf nested deepest under a root, earliest in subtask order on ties.
+    /// A parent whose subtasks are all done is active but not a leaf, so it is skipped.
+    pub fn deepest_active_leaf(&self, root_id: usize) -> Result<Option<Task>, String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&root_id) {
+            return Err(format!("Task with id: {} not found", root_id));
+        }
+
+        let mut active_ids = Vec::new();
+        let mut visited = HashSet::new();
+        Self::collect_active_ids(&tasks_map, root_id, &mut visited, &mut active_ids);
+
+        let depth_of = |task: &Task| {
+            let mut depth = 0;
+            let mut seen = HashSet::new();
+            let mut current = task;
+            while current.id != root_id && seen.insert(current.id) {
+                match current.parent.and_then(|pid| tasks_map.get(&pid)) {
+                    Some(parent) => current = parent,
+                    None => break,
+                }
+                depth += 1;
+            }
+            depth
+        };
+
+        let mut deepest: Option<(usize, &Task)> = None;
+        for id in active_ids {
+            let task = &tasks_map[&id];
+            if !task.subtasks.is_empty() {
+                continue;
+            }
+            let depth = depth_of(task);
+            if deepest.is_none_or(|(best, _)| depth > best) {
+                deepest = Some((depth, task));
+            }
+        }
+        Ok(deepest.map(|(_, task)| task.clone()))
+    }
+
+    /// Minutes of recorded work per task within `[from, to)`, most worked first.
+    pub fn time_report(&self, from: i64, to: i64) -> Vec<(usize, u32)> {
+        let mut report: Vec<(usize, u32)> = self
+            .snapshot()
+            .into_values()
+            .filter_map(|task| {
+                let seconds: i64 = task
+                    .work_sessions
+                    .iter()
+                    .map(|&(start, end)| (end.min(to) - start.max(from)).max(0))
+                    .sum();
+                (seconds > 0).then_some((task.id, (seconds / 60) as u32))
+            })
+            .collect();
+        report.sort_by_key(|&(id, minutes)| (std::cmp::Reverse(minutes), id));
+        report
+    }
+
+    /// Tasks whose text matches exactly, case included.
+    pub fn get_task_by_text(&self, text: &str) -> Vec<Task> {
+        let mut tasks: Vec<Task> = self
+            .snapshot()
+            .into_values()
+            .filter(|t| t.text == text)
+            .collect();
+        tasks.sort_by_key(|t| t.id);
+        tasks
+    }
+
+    /// Completes a task and returns the ids that became active because of it.
+    pub fn complete_task_unblocking(&self, id: usize) -> Result<Vec<usize>, String> {
+        let before: HashSet<usize> = self.compute_active_tasks().iter().map(|t| t.id).collect();
+        self.complete_task(id)?;
+        Ok(self
+            .compute_active_tasks()
+            .iter()
+            .map(|t| t.id)
+            .filter(|tid| !before.contains(tid))
+            .collect())
+    }
+
+    pub fn dump_structure(&self) -> Vec<TaskDump> {
+        let mut dump: Vec<TaskDump> = self
+            .snapshot()
+            .into_values()
+            .map(|task| TaskDump {
+                id: task.id,
+                text: task.text,
+                completed: task.completed,
+                ordered: task.ordered,
+                parent: task.parent,
+                subtasks: task.subtasks,
+                predecessors: task.predecessors,
+            })
+            .collect();
+        dump.sort_by_key(|entry| entry.id);
+        dump
+    }
+
+    // Every task reachable through predecessor links, nearest first, guarded against cycles
+    fn transitive_predecessors(tasks_map: &HashMap<usize, Task>, id: usize) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut visited = HashSet::from([id]);
+        let mut queue: VecDeque<usize> = VecDeque::from([id]);
+
+        while let Some(current_id) = queue.pop_front() {
+            let predecessors = tasks_map
+                .get(&current_id)
+                .into_iter()
+                .flat_map(Task::hard_predecessors);
+            for predecessor in predecessors {
+                if tasks_map.contains_key(&predecessor) && visited.insert(predecessor) {
+                    found.push(predecessor);
+                    queue.push_back(predecessor);
+                }
+            }
+        }
+        found
+    }
+
+    /// Completes a task along with everything it transitively depends on.
+    pub fn complete_task_with_predecessors(&self, id: usize) -> Result<(), String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&id) {
+            return Err(format!("Task with id: {} not found", id));
+        }
+
+        let mut to_complete: Vec<usize> = Self::transitive_predecessors(&tasks_map, id)
+            .into_iter()
+            .filter(|pid| !tasks_map[pid].completed)
+            .collect();
+        to_complete.push(id);
+        // Check up front so a locked task in the chain leaves everything untouched
+        if let Some(locked_id) = to_complete.iter().find(|tid| tasks_map[tid].locked) {
+            return Err(format!("Task with id: {} is locked", locked_id));
+        }
+
+        let timestamp = current_timestamp();
+        for task_id in to_complete {
+            self.complete_task_at(task_id, timestamp)?;
+        }
+        Ok(())
+    }
+
+    /// Marks a task and all its descendants incomplete again, for reusing a checklist.
+    pub fn reset_subtree(&self, id: usize) -> Result<usize, String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&id) {
+            return Err(format!("Task with id: {} not found", id));
+        }
+
+        let ids = Self::subtree_ids(&tasks_map, id);
+        let timestamp = current_timestamp();
+        {
+            // All or nothing: checked and reset under one map lock
+            let tasks = self.tasks.lock().unwrap();
+            for &task_id in &ids {
+                Self::check_unlocked(&tasks, task_id)?;
+            }
+            for task in ids.iter().filter_map(|task_id| tasks.get(task_id)) {
+                let mut task = task.lock().unwrap();
+                task.completed = false;
+                task.completed_at = None;
+                task.auto_completed = false;
+                task.stop_timer(timestamp);
+            }
+        }
+        self.mark_dirty();
+        Ok(ids.len())
+    }
+
+    /// Appends a progress note to a task's comment log.
+    pub fn add_comment(&self, id: usize, text: String, timestamp: i64) -> Result<(), String> {
+        if text.trim().is_empty() {
+            return Err("Comment must not be empty".to_string());
+        }
+        self.check_text_length(&text)?;
+        self.with_task_mut(id, |task| task.comments.push(Comment { text, timestamp }))
+    }
+
+    pub fn get_comments(&self, id: usize) -> Result<Vec<Comment>, String> {
+        self.get_task(id)
+            .map(|task| task.comments)
+            .ok_or(format!("Task with id: {} not found", id))
+    }
+
+    /// How many incomplete tasks wait on this one, directly or through a chain.
+    pub fn blocked_count(&self, id: usize) -> Result<usize, String> {
+        let tasks_map = self.snapshot();
+        let task = tasks_map
+            .get(&id)
+            .ok_or(format!("Task with id: {} not found", id))?;
+        if task.completed {
+            return Ok(0);
+        }
+
+        let mut dependents: HashMap<usize, Vec<usize>> = HashMap::new();
+        for t in tasks_map.values() {
+            for predecessor in t.hard_predecessors() {
+                dependents.entry(predecessor).or_default().push(t.id);
+            }
+        }
+
+        let mut gated = HashSet::new();
+        let mut queue = VecDeque::from([id]);
+        while let Some(current_id) = queue.pop_front() {
+            let current = &tasks_map[&current_id];
+            let mut next: Vec<usize> = dependents.get(&current_id).cloned().unwrap_or_default();
+
+            // Later steps of an ordered parent wait for this one
+            if let Some(parent) = current.parent.and_then(|pid| tasks_map.get(&pid)) {
+                if parent.ordered {
+                    next.extend(
+                        parent
+                            .subtasks
+                            .iter()
+                            .skip_while(|&&sid| sid != current_id)
+                            .skip(1),
+                    );
+                }
+            }
+            // Everything under a gated task is gated with it
+            if current_id != id {
+                next.extend(&current.subtasks);
+            }
+
+            for next_id in next {
+                if next_id != id && tasks_map.contains_key(&next_id) && gated.insert(next_id) {
+                    queue.push_back(next_id);
+                }
+            }
+        }
+
+        Ok(gated.iter().filter(|gid| !tasks_map[gid].completed).count())
+    }
+
+    /// Adds a tag to a task and all its descendants, returning how many were tagged.
+    pub fn tag_subtree(&self, root_id: usize, tag: String) -> Result<usize, String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&root_id) {
+            return Err(format!("Task with id: {} not found", root_id));
+        }
+
+        let ids = Self::subtree_ids(&tasks_map, root_id);
+        {
+            let tasks = self.tasks.lock().unwrap();
+            for &id in &ids {
+                Self::check_unlocked(&tasks, id)?;
+            }
+            for task in ids.iter().filter_map(|id| tasks.get(id)) {
+                let mut task = task.lock().unwrap();
+                if !task.tags.contains(&tag) {
+                    task.tags.push(tag.clone());
+                }
+            }
+        }
+        self.mark_dirty();
+        Ok(ids.len())
+    }
+
+    /// Active tasks whose due date passed in `(previous_now, now]`, i.e. since the last check.
+    pub fn newly_overdue(&self, previous_now: i64, now: i64) -> Vec<Task> {
+        let mut tasks: Vec<Task> = self
+            .compute_active_tasks()
+            .into_iter()
+            .filter(|t| {
+                t.due_date
+                    .is_some_and(|due| due > previous_now && due <= now)
+            })
+            .collect();
+        tasks.sort_by_key(|t| (t.due_date, t.id));
+        tasks
+    }
+
+    /// Writes a finished subtree to its own file and drops it from the live tasks.
+    pub fn archive_to_file(&self, root_id: usize, file_path: &str) -> Result<usize, String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&root_id) {
+            return Err(format!("Task with id: {} not found", root_id));
+        }
+        let ids = Self::subtree_ids(&tasks_map, root_id);
+        if let Some(open_id) = ids.iter().find(|id| !tasks_map[id].completed) {
+            return Err(format!(
+                "Cannot archive task {}: task {} is not completed",
+                root_id, open_id
+            ));
+        }
+        if let Some(locked_id) = ids.iter().find(|id| tasks_map[id].locked) {
+            return Err(format!("Task with id: {} is locked", locked_id));
+        }
+
+        let json = self.export_subtree_json(root_id)?;
+        if let Some(parent_dir) = Path::new(file_path).parent() {
+            fs::create_dir_all(parent_dir).map_err(|e| {
+                format!("Failed to create directory {}: {}", parent_dir.display(), e)
+            })?;
+        }
+        fs::write(file_path, json).map_err(|e| format!("Failed to write archive: {}", e))?;
+
+        self.remove_task_recursive(root_id)
+    }
+
+    /// The most recently created tasks, newest first, for an inbox view.
+    pub fn get_recent_tasks(&self, limit: usize) -> Vec<Task> {
+        let mut tasks: Vec<Task> = self.snapshot().into_values().collect();
+        tasks.sort_by_key(|t| std::cmp::Reverse(t.id));
+        tasks.truncate(limit);
+        tasks
+    }
+
+    /// Records an ordered parent's sequence as explicit predecessors, so it survives
+    /// turning the parent unordered.
+    pub fn freeze_order_as_dependencies(&self, parent_id: usize) -> Result<(), String> {
+        let parent = self
+            .get_task(parent_id)
+            .ok_or(format!("Task with id: {} not found", parent_id))?;
+        if !parent.ordered {
+            return Err(format!("Task with id: {} is not ordered", parent_id));
+        }
+
+        for pair in parent.subtasks.windows(2) {
+            let (previous, current) = (pair[0], pair[1]);
+            self.with_task_mut(current, |task| task.add_dependency(previous, true))?;
+        }
+        Ok(())
+    }
+
+    /// Whether anything changed since the last successful save or load.
+    pub fn has_unsaved_changes(&self) -> bool {
+        *self.dirty.lock().unwrap()
+    }
+
+    /// Every tag in use, deduplicated and sorted, for autocomplete.
+    pub fn get_all_tags(&self) -> Vec<String> {
+        let tasks = self.tasks.lock().unwrap();
+        let mut tags: Vec<String> = tasks
+            .values()
+            .flat_map(|task| task.lock().unwrap().tags.clone())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    /// Places a task right after `anchor_id` in the anchor's sibling list, reparenting if needed.
+    pub fn move_after(&self, task_id: usize, anchor_id: usize) -> Result<(), String> {
+        self.move_next_to(task_id, anchor_id, 1)
     }
 
-    pub fn update_task_text(&self, id: usize, text: String) -> Result<(), String> {
-        let mut tasks = self.tasks.lock().unwrap();
-        let task = tasks
-            .get_mut(&id)
-            .ok_or(format!("Task with id: {} not found", id))?;
-        let mut task_lock = task.lock().unwrap();
-        task_lock.text = text;
-        Ok(())
+    /// Places a task right before `anchor_id` in the anchor's sibling list, reparenting if needed.
+    pub fn move_before(&self, task_id: usize, anchor_id: usize) -> Result<(), String> {
+        self.move_next_to(task_id, anchor_id, 0)
     }
 
-    pub fn complete_task(&self, id: usize) -> Result<(), String> {
-        let task = {
+    fn move_next_to(&self, task_id: usize, anchor_id: usize, offset: usize) -> Result<(), String> {
+        let anchor_ancestors = self.get_ancestor_ids(anchor_id)?;
+        if anchor_ancestors.contains(&task_id) {
+            return Err(format!(
+                "Cannot move task {} next to itself or its own descendant {}",
+                task_id, anchor_id
+            ));
+        }
+
+        let (old_parent, new_parent) = {
             let tasks = self.tasks.lock().unwrap();
-            tasks
-                .get(&id)
-                .ok_or(format!("Task with id: {} not found", id))?
-                .clone()
+            let task = tasks
+                .get(&task_id)
+                .ok_or(format!("Task with id: {} not found", task_id))?;
+            let old_parent = task.lock().unwrap().parent;
+            let new_parent = tasks[&anchor_id].lock().unwrap().parent;
+            // The task moves out of one parent's list and into another's
+            for id in [task_id].into_iter().chain(old_parent).chain(new_parent) {
+                Self::check_unlocked(&tasks, id)?;
+            }
+
+            match old_parent.and_then(|pid| tasks.get(&pid)) {
+                Some(parent) => parent
+                    .lock()
+                    .unwrap()
+                    .subtasks
+                    .retain(|&sid| sid != task_id),
+                None => self.root_tasks.lock().unwrap().retain(|&id| id != task_id),
+            }
+
+            let insert = |siblings: &mut Vec<usize>| {
+                let index = siblings
+                    .iter()
+                    .position(|&id| id == anchor_id)
+                    .map_or(siblings.len(), |pos| pos + offset);
+                siblings.insert(index, task_id);
+            };
+            match new_parent.and_then(|pid| tasks.get(&pid)) {
+                Some(parent) => insert(&mut parent.lock().unwrap().subtasks),
+                None => insert(&mut self.root_tasks.lock().unwrap()),
+            }
+            task.lock().unwrap().parent = new_parent;
+            (old_parent, new_parent)
         };
-        task.lock().unwrap().completed = true;
+
+        self.upsert_task(task_id)?;
+        let mut reordered: Vec<usize> = old_parent.into_iter().chain(new_parent).collect();
+        reordered.dedup();
+        for parent_id in reordered {
+            self.notify(TaskEvent::Reordered(parent_id));
+        }
         Ok(())
     }
 
-    pub fn uncomplete_task(&self, id: usize) -> Result<(), String> {
-        let mut tasks = self.tasks.lock().unwrap();
-        let task = tasks
-            .get_mut(&id)
-            .ok_or(format!("Task with id: {} not found", id))?;
-        let mut task_lock = task.lock().unwrap();
-        task_lock.completed = false;
-        Ok(())
+    /// Active tasks in working order: those that unblock something first, then by priority.
+    pub fn ready_queue(&self) -> Vec<Task> {
+        let tasks_map = self.snapshot();
+
+        let mut unblockers: HashSet<usize> = HashSet::new();
+        for task in tasks_map.values().filter(|t| !t.completed) {
+            unblockers.extend(task.hard_predecessors());
+            if let Some(parent) = task.parent.and_then(|pid| tasks_map.get(&pid)) {
+                if parent.ordered {
+                    let position = parent.subtasks.iter().position(|&sid| sid == task.id);
+                    if let Some(previous) = position.filter(|&pos| pos > 0) {
+                        unblockers.insert(parent.subtasks[previous - 1]);
+                    }
+                }
+            }
+        }
+
+        let mut queue: Vec<Task> = self
+            .active_ids_in(&tasks_map)
+            .iter()
+            .map(|id| tasks_map[id].clone())
+            .collect();
+        queue.sort_by_key(|t| (!unblockers.contains(&t.id), std::cmp::Reverse(t.priority)));
+        queue
     }
 
-    pub fn toggle_ordered(&self, id: usize) -> Result<(), String> {
-        let mut tasks = self.tasks.lock().unwrap();
-        let task = tasks
-            .get_mut(&id)
+    /// Copies a task without its children, right after the original.
+    pub fn duplicate_task_shallow(&self, id: usize) -> Result<usize, String> {
+        let original = self
+            .get_task(id)
             .ok_or(format!("Task with id: {} not found", id))?;
-        let mut task_lock = task.lock().unwrap();
-        task_lock.ordered = !task_lock.ordered;
-        Ok(())
+
+        let new_id = {
+            let mut tasks = self.tasks.lock().unwrap();
+            self.check_task_limit(&tasks, 1)?;
+            let new_id = self.generate_id();
+            let mut copy = Task::new(new_id, original.text, original.ordered);
+            copy.tags = original.tags;
+            copy.priority = original.priority;
+            copy.parent = original.parent;
+
+            let insert_after = |siblings: &mut Vec<usize>| {
+                let index = siblings
+                    .iter()
+                    .position(|&sid| sid == id)
+                    .map_or(siblings.len(), |pos| pos + 1);
+                siblings.insert(index, new_id);
+            };
+            match original.parent.and_then(|pid| tasks.get(&pid)) {
+                Some(parent) => insert_after(&mut parent.lock().unwrap().subtasks),
+                None => insert_after(&mut self.root_tasks.lock().unwrap()),
+            }
+            tasks.insert(new_id, Arc::new(Mutex::new(copy)));
+            new_id
+        };
+
+        self.upsert_task(new_id)?;
+        self.notify(TaskEvent::Added(new_id));
+        Ok(new_id)
     }
 
-    // Method to adjust the order of subtasks
-    pub fn reorder_subtasks(&self, parent_id: usize, new_order: Vec<usize>) -> Result<(), String> {
-        let tasks_map = self.tasks.lock().unwrap();
-        let parent_task_arc = tasks_map
-            .get(&parent_id)
-            .ok_or(format!("Parent task with id: {} not found", parent_id))?
-            .clone();
+    pub fn get_root_tasks(&self) -> Vec<Task> {
+        let root_ids = self.root_tasks.lock().unwrap().clone();
+        let tasks = self.tasks.lock().unwrap();
+        root_ids
+            .iter()
+            .filter_map(|id| tasks.get(id))
+            .map(|task| task.lock().unwrap().clone())
+            .collect()
+    }
 
-        let mut parent_task_lock = parent_task_arc.lock().unwrap();
+    /// Reorders the root list once by the given key; `Manual` leaves it as is.
+    pub fn sort_roots(&self, by: RootSort) {
+        let tasks_map = self.snapshot();
+        // Share of a root's descendants that are done, a leaf root counts as itself
+        let progress = |root_id: usize| -> f64 {
+            let descendants: Vec<usize> = Self::subtree_ids(&tasks_map, root_id)
+                .into_iter()
+                .filter(|&id| id != root_id)
+                .collect();
+            if descendants.is_empty() {
+                let completed = tasks_map.get(&root_id).is_some_and(|t| t.completed);
+                return if completed { 1.0 } else { 0.0 };
+            }
+            let done = descendants
+                .iter()
+                .filter(|id| tasks_map[id].completed)
+                .count();
+            done as f64 / descendants.len() as f64
+        };
 
-        // Validate that new_order contains the same subtasks
-        let current_subtasks_set: HashSet<_> = parent_task_lock.subtasks.iter().cloned().collect();
-        let new_subtasks_set: HashSet<_> = new_order.iter().cloned().collect();
-        if current_subtasks_set != new_subtasks_set {
-            return Err("New order must contain the same subtasks".to_string());
+        let mut root_tasks = self.root_tasks.lock().unwrap();
+        match by {
+            RootSort::Manual => return,
+            RootSort::Alphabetical => root_tasks.sort_by_key(|id| {
+                tasks_map
+                    .get(id)
+                    .map(|t| t.text.to_lowercase())
+                    .unwrap_or_default()
+            }),
+            RootSort::ProgressAscending => {
+                root_tasks.sort_by(|a, b| progress(*a).total_cmp(&progress(*b)))
+            }
+            RootSort::DueDate => root_tasks.sort_by_key(|id| {
+                let due_date = tasks_map.get(id).and_then(|t| t.due_date);
+                (due_date.is_none(), due_date)
+            }),
         }
+        drop(root_tasks);
+        self.mark_dirty();
+    }
 
-        // Update the subtask order
-        parent_task_lock.subtasks = new_order.clone();
-        drop(parent_task_lock);
+    /// Groups of siblings whose text matches after trimming and ignoring case.
+    pub fn find_duplicates(&self) -> Vec<Vec<usize>> {
+        let mut groups: BTreeMap<(Option<usize>, String), Vec<usize>> = BTreeMap::new();
+        for task in self.snapshot().into_values() {
+            groups
+                .entry((task.parent, task.text.trim().to_lowercase()))
+                .or_default()
+                .push(task.id);
+        }
 
-        Ok(())
+        let mut duplicates: Vec<Vec<usize>> = groups
+            .into_values()
+            .filter(|ids| ids.len() > 1)
+            .map(|mut ids| {
+                ids.sort();
+                ids
+            })
+            .collect();
+        duplicates.sort();
+        duplicates
     }
 
-    pub fn get_active_tasks(&self) -> Vec<Task> {
-        // 克隆任务映射，避免持有锁
-        let tasks_map = {
+    /// Folds `merge_id` into `keep_id`: children, tags, attachments, comments and
+    /// dependencies move over, then `merge_id` is removed.
+    pub fn merge_tasks(&self, keep_id: usize, merge_id: usize) -> Result<(), String> {
+        if keep_id == merge_id {
+            return Err("Cannot merge a task into itself".to_string());
+        }
+        if self.get_ancestor_ids(keep_id)?.contains(&merge_id) {
+            return Err(format!(
+                "Cannot merge task {} into its own descendant {}",
+                merge_id, keep_id
+            ));
+        }
+        let merged = {
             let tasks = self.tasks.lock().unwrap();
-            tasks
-                .iter()
-                .map(|(&id, task_arc)| {
-                    let task_lock = task_arc.lock().unwrap();
-                    (id, task_lock.clone())
-                })
-                .collect::<HashMap<usize, Task>>()
+            let merge = tasks
+                .get(&merge_id)
+                .ok_or(format!("Task with id: {} not found", merge_id))?;
+            // Both sides change: one is emptied and removed, the other takes over its children
+            Self::check_unlocked(&tasks, merge_id)?;
+            Self::check_unlocked(&tasks, keep_id)?;
+            let mut merge_lock = merge.lock().unwrap();
+            let merged = merge_lock.clone();
+            merge_lock.subtasks.clear();
+            drop(merge_lock);
+
+            for child_id in &merged.subtasks {
+                if let Some(child) = tasks.get(child_id) {
+                    child.lock().unwrap().parent = Some(keep_id);
+                }
+            }
+            for task in tasks.values() {
+                let mut task_lock = task.lock().unwrap();
+                if task_lock.id == merge_id {
+                    continue;
+                }
+                let position = task_lock
+                    .predecessors
+                    .iter()
+                    .position(|dependency| dependency.id == merge_id);
+                if let Some(position) = position {
+                    let dependency = task_lock.predecessors.remove(position);
+                    if task_lock.id != keep_id {
+                        task_lock.add_dependency(keep_id, dependency.hard);
+                    }
+                }
+            }
+            merged
         };
 
-        let root_task_ids = {
-            let root_tasks = self.root_tasks.lock().unwrap();
-            root_tasks.clone()
-        };
+        self.with_task_mut(keep_id, |keep| {
+            keep.subtasks.extend(&merged.subtasks);
+            for tag in merged.tags {
+                if !keep.tags.contains(&tag) {
+                    keep.tags.push(tag);
+                }
+            }
+            for attachment in merged.attachments {
+                if !keep.attachments.contains(&attachment) {
+                    keep.attachments.push(attachment);
+                }
+            }
+            keep.comments.extend(merged.comments);
+            for dependency in merged.predecessors {
+                if dependency.id != keep_id {
+                    keep.add_dependency(dependency.id, dependency.hard);
+                }
+            }
+        })?;
 
-        let mut active_tasks = Vec::new();
+        self.remove_task_recursive(merge_id)?;
+        Ok(())
+    }
 
-        for root_task_id in root_task_ids {
-            if let Some(root_task) = tasks_map.get(&root_task_id) {
-                self.collect_active_tasks(root_task, &tasks_map, &mut active_tasks);
+    /// Active incomplete tasks as an iCalendar file of VTODO entries.
+    /// Our priorities grow with importance, iCalendar's 1..=9 shrink; 0 stays undefined.
+    pub fn export_ics(&self) -> String {
+        let timestamp = current_timestamp();
+        let (hours, minutes, seconds) = (
+            timestamp.rem_euclid(86_400) / 3600,
+            timestamp.rem_euclid(3600) / 60,
+            timestamp.rem_euclid(60),
+        );
+        let dtstamp = format!(
+            "{}T{:02}{:02}{:02}Z",
+            ics_date(timestamp),
+            hours,
+            minutes,
+            seconds
+        );
+
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//the-machine//EN".to_string(),
+        ];
+        for task in self.get_active_tasks().iter().filter(|t| !t.completed) {
+            lines.push("BEGIN:VTODO".to_string());
+            lines.push(format!("UID:task-{}@the-machine", task.id));
+            lines.push(format!("DTSTAMP:{}", dtstamp));
+            lines.push(format!("SUMMARY:{}", ics_escape(&task.text)));
+            if let Some(due) = task.due_date {
+                lines.push(format!("DUE;VALUE=DATE:{}", ics_date(due)));
             }
+            let priority = if task.priority == 0 {
+                0
+            } else {
+                10 - task.priority.min(9)
+            };
+            lines.push(format!("PRIORITY:{}", priority));
+            lines.push("END:VTODO".to_string());
         }
+        lines.push("END:VCALENDAR".to_string());
 
-        active_tasks
+        let folded: Vec<String> = lines.iter().map(|line| ics_fold(line)).collect();
+        let mut ics = folded.join("\r\n");
+        ics.push_str("\r\n");
+        ics
     }
 
-    fn collect_active_tasks(
-        &self,
-        task: &Task,
-        tasks_map: &HashMap<usize, Task>,
-        active_tasks: &mut Vec<Task>,
-    ) {
-        if task.completed {
-            return;
-        }
+    /// True when nothing is left to do, including when there are no tasks at all.
+    pub fn all_complete(&self) -> bool {
+        let tasks = self.tasks.lock().unwrap();
+        tasks.values().all(|task| task.lock().unwrap().completed)
+    }
 
-        if task.subtasks.is_empty() {
-            active_tasks.push(task.clone());
-            return;
+    /// Sets a fractional manual-order key, e.g. 1.5 to sit between keys 1.0 and 2.0.
+    pub fn set_sort_key(&self, id: usize, sort_key: Option<f64>) -> Result<(), String> {
+        if sort_key.is_some_and(|key| !key.is_finite()) {
+            return Err("Sort key must be a finite number".to_string());
         }
+        self.with_task_mut(id, |task| task.sort_key = sort_key)
+    }
+
+    /// The first incomplete step after `id` in its parent's ordered checklist.
+    pub fn next_sibling_active(&self, id: usize) -> Result<Option<Task>, String> {
+        let tasks_map = self.snapshot();
+        let task = tasks_map
+            .get(&id)
+            .ok_or(format!("Task with id: {} not found", id))?;
+        let parent = match task.parent.and_then(|pid| tasks_map.get(&pid)) {
+            Some(parent) if parent.ordered => parent,
+            _ => return Ok(None),
+        };
 
-        let mut all_subtasks_completed = true;
+        Ok(parent
+            .subtasks
+            .iter()
+            .skip_while(|&&sid| sid != id)
+            .skip(1)
+            .filter_map(|sid| tasks_map.get(sid))
+            .find(|sibling| !sibling.completed)
+            .cloned())
+    }
 
-        if task.ordered {
-            for &subtask_id in &task.subtasks {
-                if let Some(subtask) = tasks_map.get(&subtask_id) {
-                    if !subtask.completed {
-                        self.collect_active_tasks(subtask, tasks_map, active_tasks);
-                        all_subtasks_completed = false;
-                        break;
-                    }
-                }
+    /// Sets one priority on many tasks under a single lock, skipping missing ids.
+    pub fn set_priority_bulk(&self, ids: Vec<usize>, priority: u8) -> Result<usize, String> {
+        let mut updated = 0;
+        {
+            let tasks = self.tasks.lock().unwrap();
+            let ids: HashSet<usize> = ids.into_iter().collect();
+            for &id in &ids {
+                Self::check_unlocked(&tasks, id)?;
             }
-        } else {
-            for &subtask_id in &task.subtasks {
-                if let Some(subtask) = tasks_map.get(&subtask_id) {
-                    if !subtask.completed {
-                        self.collect_active_tasks(subtask, tasks_map, active_tasks);
-                        all_subtasks_completed = false;
-                    }
+            for id in ids {
+                if let Some(task) = tasks.get(&id) {
+                    task.lock().unwrap().priority = priority;
+                    updated += 1;
                 }
             }
         }
+        if updated > 0 {
+            self.mark_dirty();
+        }
+        Ok(updated)
+    }
 
-        if all_subtasks_completed {
-            active_tasks.push(task.clone());
+    /// Every incomplete task in a project, active or not, in pre-order.
+    pub fn remaining_tasks(&self, root_id: usize) -> Result<Vec<Task>, String> {
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&root_id) {
+            return Err(format!("Task with id: {} not found", root_id));
         }
+
+        Ok(Self::subtree_ids(&tasks_map, root_id)
+            .iter()
+            .map(|id| &tasks_map[id])
+            .filter(|task| !task.completed)
+            .cloned()
+            .collect())
     }
 
-    pub fn remove_task_recursive(&self, task_id: usize) -> Result<usize, String> {
-        let task_arc = {
-            let tasks = self.tasks.lock().unwrap();
-            tasks
-                .get(&task_id)
-                .ok_or(format!("Task with id: {} not found", task_id))?
-                .clone()
-        };
+    /// `(index, total)` of a task among its siblings, or among the roots for a root task.
+    pub fn get_position(&self, id: usize) -> Result<(usize, usize), String> {
+        let tasks_map = self.snapshot();
+        let task = tasks_map
+            .get(&id)
+            .ok_or(format!("Task with id: {} not found", id))?;
 
-        let subtasks = {
-            let task_lock = task_arc.lock().unwrap();
-            task_lock.subtasks.clone()
+        let siblings = match task.parent {
+            Some(parent_id) => tasks_map
+                .get(&parent_id)
+                .ok_or(format!("Parent task with id: {} not found", parent_id))?
+                .subtasks
+                .clone(),
+            None => self.root_tasks.lock().unwrap().clone(),
         };
+        let index = siblings.iter().position(|&sid| sid == id).ok_or(format!(
+            "Task with id: {} is missing from its parent's list",
+            id
+        ))?;
+        Ok((index, siblings.len()))
+    }
 
-        let mut delete_count = 1;
-
-        for subtask_id in subtasks {
-            delete_count += self.remove_task_recursive(subtask_id)?;
+    /// Makes `id` depend on `predecessor_id`. Only hard dependencies block activation;
+    /// soft ones are kept as hints. Setting it again switches the kind.
+    pub fn set_dependency(
+        &self,
+        id: usize,
+        predecessor_id: usize,
+        hard: bool,
+    ) -> Result<(), String> {
+        if id == predecessor_id {
+            return Err("A task cannot depend on itself".to_string());
         }
-
-        {
-            let mut tasks = self.tasks.lock().unwrap();
-            tasks.remove(&task_id);
+        if self.get_task(predecessor_id).is_none() {
+            return Err(format!("Task with id: {} not found", predecessor_id));
         }
 
-        {
-            let mut root_tasks = self.root_tasks.lock().unwrap();
-            if let Some(pos) = root_tasks.iter().position(|&id| id == task_id) {
-                root_tasks.remove(pos);
+        self.with_task_mut(id, |task| {
+            task.predecessors
+                .retain(|dependency| dependency.id != predecessor_id);
+            task.predecessors.push(Dependency {
+                id: predecessor_id,
+                hard,
+            });
+        })
+    }
+
+    /// `TaskStats` per tag from a single snapshot; a task counts once for each of its tags.
+    pub fn stats_by_tag(&self) -> HashMap<String, TaskStats> {
+        let tasks_map = self.snapshot();
+        let active_ids: HashSet<usize> = self.active_ids_in(&tasks_map).into_iter().collect();
+
+        let mut stats: HashMap<String, TaskStats> = HashMap::new();
+        for task in tasks_map.values() {
+            for tag in &task.tags {
+                let tag_stats = stats.entry(tag.clone()).or_default();
+                tag_stats.total += 1;
+                if task.completed {
+                    tag_stats.completed += 1;
+                } else if task.in_progress {
+                    tag_stats.in_progress += 1;
+                } else {
+                    tag_stats.todo += 1;
+                }
+                if active_ids.contains(&task.id) {
+                    tag_stats.active += 1;
+                }
             }
         }
-
-        Ok(delete_count)
+        stats
     }
 
-    pub fn get_subtasks_recursive(&self, id: usize, max_count: usize) -> Result<Vec<Task>, String> {
-        let task = {
-            let tasks = self.tasks.lock().unwrap();
-            tasks
-                .get(&id)
-                .ok_or(format!("Task with id: {} not found", id))?
-                .clone()
-        };
-
-        let subtasks_ids = {
-            let task_lock = task.lock().unwrap();
-            task_lock.subtasks.clone()
-        };
+    /// Moves several siblings as one block to `to_index`, keeping their relative order.
+    /// `to_index` counts positions among the remaining siblings, like `reorder_move`.
+    pub fn move_subtasks(
+        &self,
+        parent_id: Option<usize>,
+        ids: Vec<usize>,
+        to_index: usize,
+    ) -> Result<(), String> {
+        let move_block = |siblings: &mut Vec<usize>| -> Result<(), String> {
+            let mut selected = HashSet::new();
+            for &id in &ids {
+                if !siblings.contains(&id) {
+                    return Err(format!("Task with id: {} is not in this list", id));
+                }
+                if !selected.insert(id) {
+                    return Err(format!("Task with id: {} is listed twice", id));
+                }
+            }
 
-        let tasks_map = {
-            let tasks = self.tasks.lock().unwrap();
-            tasks.clone()
+            let block: Vec<usize> = siblings
+                .iter()
+                .copied()
+                .filter(|id| selected.contains(id))
+                .collect();
+            siblings.retain(|id| !selected.contains(id));
+            let index = to_index.min(siblings.len());
+            siblings.splice(index..index, block);
+            Ok(())
         };
 
-        let mut subtasks: Vec<Task> = Vec::new();
-        let mut subtasks_to_process = subtasks_ids;
-        let mut processed_count = 0;
-
-        while let Some(subtask_id) = subtasks_to_process.pop() {
-            if let Some(subtask) = tasks_map.get(&subtask_id) {
-                subtasks.push(subtask.lock().unwrap().clone());
-                processed_count += 1;
-
-                if processed_count >= max_count {
-                    break;
+        match parent_id {
+            Some(parent_id) => {
+                {
+                    let tasks = self.tasks.lock().unwrap();
+                    let parent = tasks
+                        .get(&parent_id)
+                        .ok_or(format!("Parent task with id: {} not found", parent_id))?;
+                    Self::check_unlocked(&tasks, parent_id)?;
+                    move_block(&mut parent.lock().unwrap().subtasks)?;
                 }
-
-                let subtask_lock = subtask.lock().unwrap();
-                subtasks_to_process.extend(subtask_lock.subtasks.iter().cloned());
+                self.upsert_task(parent_id)?;
+                self.notify(TaskEvent::Reordered(parent_id));
+            }
+            None => {
+                move_block(&mut self.root_tasks.lock().unwrap())?;
+                self.mark_dirty();
             }
         }
 
-        Ok(subtasks)
+        Ok(())
     }
 
-    pub fn get_subtasks(&self, id: usize) -> Result<Vec<Task>, String> {
-        let task = {
-            let tasks = self.tasks.lock().unwrap();
-            tasks
-                .get(&id)
-                .ok_or(format!("Task with id: {} not found", id))?
-                .clone()
-        };
+    /// Cumulative completed count within a subtree at each completion time, oldest first.
+    pub fn subtree_completion_timeline(&self, root_id: usize) -> Vec<(i64, usize)> {
+        let tasks_map = self.snapshot();
+        let mut completions: Vec<i64> = Self::subtree_ids(&tasks_map, root_id)
+            .iter()
+            .map(|id| &tasks_map[id])
+            .filter(|task| task.completed)
+            .filter_map(|task| task.completed_at)
+            .collect();
+        completions.sort();
+
+        let mut timeline: Vec<(i64, usize)> = Vec::new();
+        for (count, timestamp) in completions.into_iter().enumerate() {
+            match timeline.last_mut() {
+                // Completions sharing a timestamp make a single point
+                Some(last) if last.0 == timestamp => last.1 = count + 1,
+                _ => timeline.push((timestamp, count + 1)),
+            }
+        }
+        timeline
+    }
 
-        let subtasks_ids = {
-            let task_lock = task.lock().unwrap();
-            task_lock.subtasks.clone()
-        };
+    /// Starts a fresh, incomplete copy of a project as a new root named `new_name`,
+    /// e.g. the next "Sprint". Tasks keep their fields and structure, but not their
+    /// completion or tracked time. Dependencies within the project point at the copies.
+    pub fn clone_project(&self, root_id: usize, new_name: String) -> Result<usize, String> {
+        self.check_text_length(&new_name)?;
+        let tasks_map = self.snapshot();
+        if !tasks_map.contains_key(&root_id) {
+            return Err(format!("Task with id: {} not found", root_id));
+        }
+        let ids = Self::subtree_ids(&tasks_map, root_id);
 
-        let tasks_map = {
-            let tasks = self.tasks.lock().unwrap();
-            tasks.clone()
+        let new_ids: Vec<usize> = {
+            let mut tasks = self.tasks.lock().unwrap();
+            self.check_task_limit(&tasks, ids.len())?;
+            let id_map: HashMap<usize, usize> =
+                ids.iter().map(|&id| (id, self.generate_id())).collect();
+            let now = current_timestamp();
+
+            for &id in &ids {
+                let mut task = tasks_map[&id].clone();
+                task.id = id_map[&id];
+                task.subtasks = task
+                    .subtasks
+                    .iter()
+                    .filter_map(|sid| id_map.get(sid).copied())
+                    .collect();
+                for dependency in &mut task.predecessors {
+                    dependency.id = *id_map.get(&dependency.id).unwrap_or(&dependency.id);
+                }
+                if id == root_id {
+                    task.text = new_name.clone();
+                    task.parent = None;
+                } else {
+                    task.parent = task.parent.and_then(|pid| id_map.get(&pid).copied());
+                }
+
+                task.completed = false;
+                task.completed_at = None;
+                task.auto_completed = false;
+                task.in_progress = false;
+                task.started_at = None;
+                task.work_sessions.clear();
+                task.created_at = Some(now);
+                tasks.insert(task.id, Arc::new(Mutex::new(task)));
+            }
+
+            self.root_tasks.lock().unwrap().push(id_map[&root_id]);
+            ids.iter().map(|id| id_map[id]).collect()
         };
 
-        let subtasks: Vec<Task> = subtasks_ids
-            .iter()
-            .filter_map(|&sid| tasks_map.get(&sid))
-            .map(|t| t.lock().unwrap().clone())
-            .collect();
-        Ok(subtasks)
+        self.mark_dirty();
+        for &id in &new_ids {
+            self.notify(TaskEvent::Added(id));
+        }
+        Ok(new_ids[0])
     }
 
-    pub fn get_parent_tasks(&self, task_id: usize) -> Result<Vec<Task>, String> {
-        let mut hierarchy = Vec::new();
-        let mut current_task_id = Some(task_id);
+    /// Tasks completed in `[from, to)`, oldest first, for weekly reviews.
+    pub fn tasks_completed_in_range(&self, from: i64, to: i64) -> Vec<Task> {
+        let mut tasks: Vec<Task> = self
+            .snapshot()
+            .into_values()
+            .filter(|t| t.completed && t.completed_at.is_some_and(|ts| (from..to).contains(&ts)))
+            .collect();
+        tasks.sort_by_key(|t| (t.completed_at, t.id));
+        tasks
+    }
 
-        while let Some(id) = current_task_id {
-            let task = {
-                let tasks = self.tasks.lock().unwrap();
-                tasks
-                    .get(&id)
-                    .ok_or(format!("Task with id: {} not found", id))?
-                    .clone()
-            };
+    /// (id, completion timestamp) pairs in `[from, to)`, oldest first, for burndown charts.
+    pub fn completions_between(&self, from: i64, to: i64) -> Vec<(usize, i64)> {
+        self.tasks_completed_in_range(from, to)
+            .into_iter()
+            .filter_map(|t| t.completed_at.map(|ts| (t.id, ts)))
+            .collect()
+    }
 
-            let task_lock = task.lock().unwrap();
-            hierarchy.push(task_lock.clone());
+    /// Tasks created in `[from, to)`, oldest first.
+    pub fn tasks_created_in_range(&self, from: i64, to: i64) -> Vec<Task> {
+        let mut tasks: Vec<Task> = self
+            .snapshot()
+            .into_values()
+            .filter(|t| t.created_at.is_some_and(|ts| (from..to).contains(&ts)))
+            .collect();
+        tasks.sort_by_key(|t| (t.created_at, t.id));
+        tasks
+    }
 
-            current_task_id = task_lock.parent;
+    /// Folds a parent's leaf subtasks into its text as `Parent: a; b; c` and removes them.
+    /// The parent ends up completed only if every subtask was.
+    pub fn collapse_subtasks(&self, parent_id: usize) -> Result<(), String> {
+        let tasks_map = self.snapshot();
+        let parent = tasks_map
+            .get(&parent_id)
+            .ok_or(format!("Parent task with id: {} not found", parent_id))?;
+        let children: Vec<&Task> = parent
+            .subtasks
+            .iter()
+            .filter_map(|sid| tasks_map.get(sid))
+            .collect();
+        if children.is_empty() {
+            return Err(format!("Task with id: {} has no subtasks", parent_id));
+        }
+        // Collapsing deeper levels would silently drop their tasks
+        if let Some(child) = children.iter().find(|child| !child.subtasks.is_empty()) {
+            return Err(format!(
+                "Subtask with id: {} has subtasks of its own",
+                child.id
+            ));
+        }
+        if let Some(locked) = std::iter::once(parent)
+            .chain(children.iter().copied())
+            .find(|task| task.locked)
+        {
+            return Err(format!("Task with id: {} is locked", locked.id));
         }
 
-        Ok(hierarchy)
+        let texts: Vec<&str> = children.iter().map(|child| child.text.as_str()).collect();
+        let combined = format!("{}: {}", parent.text, texts.join("; "));
+        self.check_text_length(&combined)?;
+        let all_done = children.iter().all(|child| child.completed);
+        let last_completed_at = children.iter().filter_map(|child| child.completed_at).max();
+
+        for child in &children {
+            self.remove_task_recursive(child.id)?;
+        }
+        let newly_completed = self.with_unlocked_task(parent_id, |task| {
+            task.text = combined;
+            let newly_completed = all_done && !task.completed;
+            if newly_completed {
+                task.completed = true;
+                task.completed_at = last_completed_at.or(Some(current_timestamp()));
+            }
+            newly_completed
+        })?;
+        if newly_completed {
+            self.notify(TaskEvent::Completed(parent_id));
+        }
+        Ok(())
     }
 
-    pub fn get_task(&self, id: usize) -> Option<Task> {
-        let tasks = self.tasks.lock().unwrap();
-        tasks.get(&id).map(|t| t.lock().unwrap().clone())
+    /// The task, its nearest incomplete siblings on either side and its parent.
+    /// Root tasks take their siblings from the root list.
+    pub fn get_task_context(&self, id: usize) -> Result<TaskContext, String> {
+        let tasks_map = self.snapshot();
+        let task = tasks_map
+            .get(&id)
+            .ok_or(format!("Task with id: {} not found", id))?;
+        let parent = task.parent.and_then(|pid| tasks_map.get(&pid));
+        let siblings = match parent {
+            Some(parent) => parent.subtasks.clone(),
+            None => self.root_tasks.lock().unwrap().clone(),
+        };
+
+        let position = siblings.iter().position(|&sid| sid == id);
+        let (before, after) = match position {
+            Some(pos) => (&siblings[..pos], &siblings[pos + 1..]),
+            None => (&siblings[..0], &siblings[..0]),
+        };
+        let incomplete = |sid: &usize| tasks_map.get(sid).filter(|t| !t.completed).cloned();
+
+        Ok(TaskContext {
+            task: task.clone(),
+            previous: before.iter().rev().find_map(incomplete),
+            next: after.iter().find_map(incomplete),
+            parent: parent.cloned(),
+        })
     }
 }
+
+#[cfg(test)]
+#[path = "../tests/task_manager_tests.rs"]
+mod tests;
@@ -2,9 +2,15 @@ pub mod commands;
 pub mod core;
 
 use commands::task_commands::*;
-use core::task_manager::TaskManager;
-use std::{path::PathBuf, sync::Arc, time::Duration};
-use tauri::async_runtime;
+use core::task_manager::{should_autosave, TaskManager};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+use tauri::{async_runtime, Emitter};
 use tokio::time::sleep;
 
 fn get_data_file_path() -> PathBuf {
@@ -12,24 +18,97 @@ fn get_data_file_path() -> PathBuf {
     app_dir.join("task_manager_data.json")
 }
 
+#[derive(Debug, Serialize)]
+pub struct DataFileInfo {
+    path: String,
+    exists: bool,
+    size_bytes: u64,
+    modified: Option<u64>,
+}
+
+fn file_info(path: &Path) -> DataFileInfo {
+    match fs::metadata(path) {
+        Ok(metadata) => DataFileInfo {
+            path: path.display().to_string(),
+            exists: true,
+            size_bytes: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs()),
+        },
+        Err(_) => DataFileInfo {
+            path: path.display().to_string(),
+            exists: false,
+            size_bytes: 0,
+            modified: None,
+        },
+    }
+}
+
+#[tauri::command]
+async fn data_file_info() -> Result<DataFileInfo, String> {
+    Ok(file_info(&get_data_file_path()))
+}
+
+/// Saves immediately instead of waiting for the auto-save timer or window close.
+#[tauri::command]
+async fn save_now(task_manager: tauri::State<'_, Arc<TaskManager>>) -> Result<(), String> {
+    let file_path = get_data_file_path();
+    task_manager.save_to_file(file_path.to_str().unwrap())
+}
+
 /// Initializes the task manager as a Tauri state.
+/// Setting `THE_MACHINE_EPHEMERAL` starts a scratch session that is never saved.
 fn init_task_manager() -> Arc<TaskManager> {
+    if std::env::var_os("THE_MACHINE_EPHEMERAL").is_some() {
+        return Arc::new(TaskManager::new_ephemeral());
+    }
+
     let task_manager = Arc::new(TaskManager::new());
 
     let file_path = get_data_file_path();
-    if let Err(e) = task_manager.load_from_file(file_path.to_str().unwrap()) {
-        println!("Failed to load data: {}", e);
+    match task_manager.load_from_file(file_path.to_str().unwrap()) {
+        Ok(()) => {
+            let repairs = task_manager.load_repairs();
+            if !repairs.is_empty() {
+                println!(
+                    "Repaired {} problem(s) in {}: {}",
+                    repairs.len(),
+                    file_path.display(),
+                    repairs.join("; ")
+                );
+            }
+        }
+        Err(e) => println!("Failed to load data: {}", e),
     }
     task_manager
 }
 
-fn start_auto_save(task_manager: Arc<TaskManager>, interval: Duration) {
+/// Writes only when something changed: at each `interval`, or sooner once a change
+/// has been pending for `max_defer`.
+fn start_auto_save(task_manager: Arc<TaskManager>, interval: Duration, max_defer: Duration) {
+    let tick = interval.min(max_defer).min(Duration::from_secs(5));
     async_runtime::spawn(async move {
+        let mut interval_start = Instant::now();
         loop {
-            sleep(interval).await;
-            let file_path = get_data_file_path();
+            sleep(tick).await;
+            let since_interval = interval_start.elapsed();
+            if since_interval >= interval {
+                interval_start = Instant::now();
+            }
 
-            if let Err(e) = task_manager.save_to_file(file_path.to_str().unwrap()) {
+            if !should_autosave(
+                task_manager.unsaved_for(),
+                since_interval,
+                interval,
+                max_defer,
+            ) {
+                continue;
+            }
+            let file_path = get_data_file_path();
+            if let Err(e) = task_manager.save_if_dirty(file_path.to_str().unwrap()) {
                 println!("Auto-save failed: {}", e);
             }
         }
@@ -40,11 +119,26 @@ fn start_auto_save(task_manager: Arc<TaskManager>, interval: Duration) {
 pub fn run() {
     let task_manager = init_task_manager();
     let task_manager_clone = Arc::clone(&task_manager);
-    start_auto_save(Arc::clone(&task_manager), Duration::from_secs(300));
+    if !task_manager.is_ephemeral() {
+        start_auto_save(
+            Arc::clone(&task_manager),
+            Duration::from_secs(300),
+            Duration::from_secs(60),
+        );
+    }
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .manage(task_manager)
+        .manage(Arc::clone(&task_manager))
+        .setup(move |app| {
+            let app_handle = app.handle().clone();
+            task_manager.subscribe(Box::new(move |event| {
+                if let Err(e) = app_handle.emit("task-event", event) {
+                    println!("Failed to emit task event: {}", e);
+                }
+            }));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             commands::task_commands::add_task,
             add_subtask,
@@ -57,10 +151,118 @@ pub fn run() {
             get_task,
             reorder_subtasks,
             remove_task,
-            update_task
+            update_task,
+            get_dependencies,
+            get_dependents,
+            set_max_tasks,
+            save_as_template,
+            instantiate_template,
+            get_subtask_labels,
+            begin_focus,
+            focus_set,
+            end_focus,
+            import_outline,
+            promote_to_root,
+            get_active_tasks_for_root,
+            set_waiting,
+            get_waiting_tasks,
+            get_recently_completed,
+            apply_reorders,
+            get_tasks,
+            export_active_plaintext,
+            set_priority,
+            set_due_date,
+            add_tag,
+            remove_tag,
+            filter_tasks,
+            rename_tag,
+            get_active_tasks_page,
+            export_graph,
+            export_subtree_json,
+            get_ancestor_ids,
+            reorder_move,
+            add_attachment,
+            remove_attachment,
+            would_complete_project,
+            quick_add,
+            set_max_text_length,
+            get_agenda,
+            get_siblings,
+            start_task,
+            stop_task,
+            get_in_progress_tasks,
+            get_stats,
+            upsert_task,
+            get_orphans,
+            critical_path,
+            set_auto_complete_parents,
+            export_state_json,
+            set_recurrence,
+            preview_occurrences,
+            find_cycles,
+            remove_tasks,
+            get_subtasks_sorted,
+            validate_reorder,
+            incomplete_counts_by_root,
+            delete_keep_children,
+            lock_task,
+            unlock_task,
+            estimate_finish_date,
+            swap_tasks,
+            save_now,
+            get_effective_tags,
+            get_tasks_by_tag,
+            deepest_active_leaf,
+            time_report,
+            get_task_by_text,
+            dump_structure,
+            complete_task_with_predecessors,
+            reset_subtree,
+            get_active_ids,
+            add_comment,
+            get_comments,
+            blocked_count,
+            tag_subtree,
+            newly_overdue,
+            archive_to_file,
+            get_recent_tasks,
+            freeze_order_as_dependencies,
+            has_unsaved_changes,
+            get_all_tags,
+            move_after,
+            move_before,
+            ready_queue,
+            duplicate_task_shallow,
+            get_root_tasks,
+            sort_roots,
+            find_duplicates,
+            merge_tasks,
+            export_ics,
+            all_complete,
+            set_sort_key,
+            next_sibling_active,
+            set_priority_bulk,
+            remaining_tasks,
+            get_position,
+            set_dependency,
+            stats_by_tag,
+            move_subtasks,
+            subtree_completion_timeline,
+            clone_project,
+            tasks_completed_in_range,
+            completions_between,
+            tasks_created_in_range,
+            collapse_subtasks,
+            check_integrity,
+            validate_and_repair,
+            get_task_context,
+            data_file_info
         ])
         .on_window_event(move |_, event| {
             if let tauri::WindowEvent::CloseRequested { .. } = event {
+                if task_manager_clone.is_ephemeral() {
+                    return;
+                }
                 let file_path = get_data_file_path();
                 if let Err(e) = task_manager_clone.save_to_file(file_path.to_str().unwrap()) {
                     println!("Failed to save data on window close: {}", e);
@@ -70,3 +272,27 @@ pub fn run() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_info_reports_size_and_existence() {
+        let path = std::env::temp_dir().join(format!(
+            "the_machine_{}_file_info_test.json",
+            std::process::id()
+        ));
+        fs::write(&path, b"{\"tasks\":[]}").unwrap();
+
+        let info = file_info(&path);
+        assert!(info.exists);
+        assert_eq!(info.size_bytes, 12);
+        assert!(info.modified.is_some());
+
+        fs::remove_file(&path).unwrap();
+        let info = file_info(&path);
+        assert!(!info.exists);
+        assert_eq!(info.size_bytes, 0);
+    }
+}
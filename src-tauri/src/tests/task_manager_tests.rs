@@ -1,205 +1,3145 @@
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::core::task_manager::TaskManager;
-    use std::collections::HashSet;
-
-    #[test]
-    fn test_add_and_retrieve_task() {
-        let manager = TaskManager::new();
-        let task_id = manager.add_task("Test Task".to_string(), true);
-        let task = manager.get_task(task_id).unwrap();
-        assert_eq!(task.id, task_id);
-        assert_eq!(task.text, "Test Task");
-        assert!(task.subtasks.is_empty());
-    }
-
-    #[test]
-    fn test_add_subtasks_and_predecessors() {
-        let manager = TaskManager::new();
-        let parent_id = manager.add_task("Parent Task".to_string(), true);
-
-        let subtask1_id = manager
-            .add_subtask(parent_id, "Subtask 1".to_string())
-            .unwrap();
-        let subtask2_id = manager
-            .add_subtask(parent_id, "Subtask 2".to_string())
-            .unwrap();
+use crate::core::task_manager::{Dependency, TaskManager, TaskManagerData};
+use std::collections::HashSet;
+
+// Unique per process and test, so concurrent runs and other checkouts never share a file
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("the_machine_{}_{}", std::process::id(), name))
+}
+
+#[test]
+fn test_add_and_retrieve_task() {
+    let manager = TaskManager::new();
+    let task_id = manager.add_task("Test Task".to_string(), true).unwrap();
+    let task = manager.get_task(task_id).unwrap();
+    assert_eq!(task.id, task_id);
+    assert_eq!(task.text, "Test Task");
+    assert!(task.subtasks.is_empty());
+}
+
+#[test]
+fn test_add_subtasks_and_predecessors() {
+    let manager = TaskManager::new();
+    let parent_id = manager.add_task("Parent Task".to_string(), true).unwrap();
+
+    let subtask1_id = manager
+        .add_subtask(parent_id, "Subtask 1".to_string())
+        .unwrap();
+    let subtask2_id = manager
+        .add_subtask(parent_id, "Subtask 2".to_string())
+        .unwrap();
+
+    let subtask1 = manager.get_task(subtask1_id).unwrap();
+    let subtask2 = manager.get_task(subtask2_id).unwrap();
+
+    // Sibling order in an ordered parent is structural, not stored as predecessors
+    assert!(subtask1.predecessors.is_empty());
+    assert!(subtask2.predecessors.is_empty());
+
+    let parent_task = manager.get_task(parent_id).unwrap();
+    assert_eq!(parent_task.subtasks, vec![subtask1_id, subtask2_id]);
+
+    // Subtask 2 still waits for Subtask 1
+    let active: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active, vec![subtask1_id]);
+    manager.complete_task(subtask1_id).unwrap();
+    let active: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active, vec![subtask2_id]);
+}
+
+#[test]
+fn test_get_active_tasks_complex() {
+    let manager = TaskManager::new();
+
+    // Create main tasks
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap(); // Ordered
+    let task_b = manager.add_task("Task B".to_string(), false).unwrap(); // Unordered
+    let task_c = manager.add_task("Task C".to_string(), true).unwrap(); // Ordered
+
+    // Add subtasks to Task A
+    let task_a1 = manager.add_subtask(task_a, "Task A1".to_string()).unwrap();
+    let task_a2 = manager.add_subtask(task_a, "Task A2".to_string()).unwrap();
+    let task_a3 = manager.add_subtask(task_a, "Task A3".to_string()).unwrap();
+
+    // Add subtasks to Task B
+    let task_b1 = manager.add_subtask(task_b, "Task B1".to_string()).unwrap();
+    let task_b2 = manager.add_subtask(task_b, "Task B2".to_string()).unwrap();
+
+    // Add subtasks to Task C
+    let task_c1 = manager.add_subtask(task_c, "Task C1".to_string()).unwrap();
+    let task_c2 = manager.add_subtask(task_c, "Task C2".to_string()).unwrap();
+
+    // Add dependencies
+    // Task A3 depends on Task B2
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let task_a3_arc = tasks.get(&task_a3).unwrap().clone();
+        let mut task_a3_lock = task_a3_arc.lock().unwrap();
+        task_a3_lock.add_dependency(task_b2, true);
+    }
+
+    // Task B2 depends on Task C
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let task_b2_arc = tasks.get(&task_b2).unwrap().clone();
+        let mut task_b2_lock = task_b2_arc.lock().unwrap();
+        task_b2_lock.add_dependency(task_c, true);
+    }
+
+    // Check initial active tasks
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+
+    // Expected active tasks: Task A1, Task B1, Task C1
+    let expected_active = vec![task_a1, task_b1, task_c1];
+    let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
+
+    assert_eq!(active_task_ids, expected_active_set);
+
+    // Complete Task A1
+    manager.complete_task(task_a1).unwrap();
+
+    // Now, Task A2 should become active
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+
+    let expected_active = vec![task_a2, task_b1, task_c1];
+    let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
+    assert_eq!(active_task_ids, expected_active_set);
+
+    // Complete Task B1
+    manager.complete_task(task_b1).unwrap();
+
+    // B2 is blocked while Task C is incomplete
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+
+    let expected_active = vec![task_a2, task_c1];
+    let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
+    assert_eq!(active_task_ids, expected_active_set);
+
+    // Complete Task C1
+    manager.complete_task(task_c1).unwrap();
+
+    // Task C2 becomes active
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+
+    let expected_active = vec![task_a2, task_c2];
+    let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
+    assert_eq!(active_task_ids, expected_active_set);
+
+    // Complete Task C2
+    manager.complete_task(task_c2).unwrap();
+
+    // With all its subtasks done, Task C itself is active
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+
+    let expected_active = vec![task_a2, task_c];
+    let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
+    assert_eq!(active_task_ids, expected_active_set);
+
+    // Complete Task C
+    manager.complete_task(task_c).unwrap();
+
+    // Task B2's dependency on Task C is satisfied
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+
+    let expected_active = vec![task_a2, task_b2];
+    let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
+    assert_eq!(active_task_ids, expected_active_set);
+
+    // Complete Task B2, then Task B which is all done
+    manager.complete_task(task_b2).unwrap();
+    manager.complete_task(task_b).unwrap();
+
+    // Task A2 remains active
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+
+    let expected_active = vec![task_a2];
+    let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
+    assert_eq!(active_task_ids, expected_active_set);
+
+    // Complete Task A2
+    manager.complete_task(task_a2).unwrap();
+
+    // Task A3 depends on B2 (which is completed), so it becomes active
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+
+    let expected_active = vec![task_a3];
+    let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
+    assert_eq!(active_task_ids, expected_active_set);
+
+    // Toggle Task A to unordered
+    manager.toggle_ordered(task_a).unwrap();
+
+    // Now, Task A3 should have no internal predecessors due to order
+    // Since its explicit predecessor B2 is completed, Task A3 remains active
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+    assert_eq!(active_task_ids, expected_active_set);
+
+    // Reorder subtasks of Task A
+    manager
+        .reorder_subtasks(task_a, vec![task_a3, task_a1, task_a2])
+        .unwrap();
+
+    // Complete Task A3
+    manager.complete_task(task_a3).unwrap();
+
+    // All of Task A's subtasks are completed, so Task A itself is what's left
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: Vec<usize> = active_tasks.iter().map(|t| t.id).collect();
+    assert_eq!(active_task_ids, vec![task_a]);
+
+    // Uncomplete Task A1
+    manager.uncomplete_task(task_a1, false).unwrap();
+
+    // Now, Task A1 should be active again
+    let active_tasks = manager.get_active_tasks();
+    let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+
+    let expected_active = vec![task_a1];
+    let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
+    assert_eq!(active_task_ids, expected_active_set);
+
+    // Complete Task A1, then Task A
+    manager.complete_task(task_a1).unwrap();
+    manager.complete_task(task_a).unwrap();
+
+    // All tasks should now be completed
+    let active_tasks = manager.get_active_tasks();
+    assert!(active_tasks.is_empty());
+}
+
+#[test]
+fn test_get_parent_tasks() {
+    let manager = TaskManager::new();
+    let parent_id = manager.add_task("Parent Task".to_string(), true).unwrap();
+    let subtask_id = manager
+        .add_subtask(parent_id, "Subtask".to_string())
+        .unwrap();
+    let hierarchy = manager.get_parent_tasks(subtask_id).unwrap();
+    assert_eq!(hierarchy.len(), 2);
+    assert_eq!(hierarchy[0].text, "Subtask");
+    assert_eq!(hierarchy[1].text, "Parent Task");
+}
+
+#[test]
+fn test_subscribers_receive_events() {
+    use crate::core::task_manager::TaskEvent;
+    use std::sync::{Arc, Mutex};
+
+    let manager = TaskManager::new();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&events);
+    manager.subscribe(Box::new(move |event| {
+        recorded.lock().unwrap().push(event.clone());
+    }));
+
+    let parent_id = manager.add_task("Parent Task".to_string(), true).unwrap();
+    let subtask1_id = manager
+        .add_subtask(parent_id, "Subtask 1".to_string())
+        .unwrap();
+    let subtask2_id = manager
+        .add_subtask(parent_id, "Subtask 2".to_string())
+        .unwrap();
+    manager.complete_task(subtask1_id).unwrap();
+    manager
+        .reorder_subtasks(parent_id, vec![subtask2_id, subtask1_id])
+        .unwrap();
+    manager.remove_task_recursive(subtask2_id).unwrap();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            TaskEvent::Added(parent_id),
+            TaskEvent::Added(subtask1_id),
+            TaskEvent::Added(subtask2_id),
+            TaskEvent::Completed(subtask1_id),
+            TaskEvent::Reordered(parent_id),
+            TaskEvent::Removed(subtask2_id),
+        ]
+    );
+}
+
+#[test]
+fn test_get_subtasks_reports_dangling_reference() {
+    let manager = TaskManager::new();
+    let parent_id = manager.add_task("Parent Task".to_string(), true).unwrap();
+    let subtask1_id = manager
+        .add_subtask(parent_id, "Subtask 1".to_string())
+        .unwrap();
+    let subtask2_id = manager
+        .add_subtask(parent_id, "Subtask 2".to_string())
+        .unwrap();
+
+    let subtasks = manager.get_subtasks(parent_id).unwrap();
+    let ids: Vec<usize> = subtasks.iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![subtask1_id, subtask2_id]);
+
+    // Corrupt the parent by pointing it at a task that doesn't exist
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let parent_arc = tasks.get(&parent_id).unwrap().clone();
+        parent_arc.lock().unwrap().subtasks.push(999);
+    }
+
+    let err = manager.get_subtasks(parent_id).unwrap_err();
+    assert!(err.contains("999"));
+}
+
+#[test]
+fn test_get_dependencies_and_dependents() {
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), false).unwrap();
+    let task_a3 = manager.add_subtask(task_a, "Task A3".to_string()).unwrap();
+    let task_b2 = manager.add_subtask(task_b, "Task B2".to_string()).unwrap();
+
+    // Task A3 depends on Task B2
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let task_a3_arc = tasks.get(&task_a3).unwrap().clone();
+        let mut task_a3_lock = task_a3_arc.lock().unwrap();
+        task_a3_lock.add_dependency(task_b2, true);
+    }
+
+    let dependencies = manager.get_dependencies(task_a3).unwrap();
+    assert_eq!(dependencies.len(), 1);
+    assert_eq!(dependencies[0].id, task_b2);
+
+    let dependents = manager.get_dependents(task_b2).unwrap();
+    assert_eq!(dependents.len(), 1);
+    assert_eq!(dependents[0].id, task_a3);
+
+    assert!(manager.get_dependencies(task_b2).unwrap().is_empty());
+    assert!(manager.get_dependents(task_a3).unwrap().is_empty());
+}
+
+#[test]
+fn test_max_tasks_limit() {
+    let manager = TaskManager::new();
+    manager.set_max_tasks(Some(2));
+
+    let first = manager.add_task("First".to_string(), true).unwrap();
+    manager.add_subtask(first, "Second".to_string()).unwrap();
+
+    assert!(manager.add_task("Third".to_string(), true).is_err());
+    assert!(manager.add_subtask(first, "Third".to_string()).is_err());
+    assert_eq!(manager.tasks.lock().unwrap().len(), 2);
+
+    manager.set_max_tasks(None);
+    assert!(manager.add_task("Third".to_string(), true).is_ok());
+}
+
+#[test]
+fn test_max_tasks_limit_checks_bulk_creation_up_front() {
+    let manager = TaskManager::new();
+    let project = manager.add_task("Project".to_string(), true).unwrap();
+    manager.add_subtask(project, "Step".to_string()).unwrap();
+    manager
+        .save_as_template(project, "project".to_string())
+        .unwrap();
+    manager.set_max_tasks(Some(3));
+
+    // Each of these would fit partially, but nothing may be created
+    assert!(manager.import_outline("One\n  Two\n", None).is_err());
+    assert!(manager.instantiate_template("project", None).is_err());
+    assert!(manager.clone_project(project, "Copy".to_string()).is_err());
+    assert_eq!(manager.tasks.lock().unwrap().len(), 2);
+
+    manager.import_outline("One\n", None).unwrap();
+    assert_eq!(manager.tasks.lock().unwrap().len(), 3);
+}
+
+#[test]
+fn test_save_and_instantiate_template() {
+    let manager = TaskManager::new();
+    let release = manager.add_task("Release".to_string(), true).unwrap();
+    let build = manager.add_subtask(release, "Build".to_string()).unwrap();
+    manager.add_subtask(build, "Compile".to_string()).unwrap();
+    manager.add_subtask(release, "Publish".to_string()).unwrap();
+    manager.complete_task(build).unwrap();
+
+    manager
+        .save_as_template(release, "release".to_string())
+        .unwrap();
+
+    let other = manager.add_task("Other".to_string(), false).unwrap();
+    let copy = manager
+        .instantiate_template("release", Some(other))
+        .unwrap();
+
+    let copy_task = manager.get_task(copy).unwrap();
+    assert_ne!(copy, release);
+    assert_eq!(copy_task.text, "Release");
+    assert_eq!(copy_task.parent, Some(other));
+
+    let children = manager.get_subtasks(copy).unwrap();
+    let texts: Vec<&str> = children.iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(texts, vec!["Build", "Publish"]);
+    assert!(children.iter().all(|t| !t.completed));
+    assert_ne!(children[0].id, build);
+
+    let grandchildren = manager.get_subtasks(children[0].id).unwrap();
+    assert_eq!(grandchildren.len(), 1);
+    assert_eq!(grandchildren[0].text, "Compile");
+
+    assert!(manager.instantiate_template("missing", None).is_err());
+}
+
+#[test]
+fn test_get_subtask_labels() {
+    let manager = TaskManager::new();
+    let parent_id = manager.add_task("Parent Task".to_string(), false).unwrap();
+    let subtask1_id = manager
+        .add_subtask(parent_id, "Subtask 1".to_string())
+        .unwrap();
+    let subtask2_id = manager
+        .add_subtask(parent_id, "Subtask 2".to_string())
+        .unwrap();
+    manager.complete_task(subtask2_id).unwrap();
+
+    let labels = manager.get_subtask_labels(parent_id).unwrap();
+    let expected: Vec<(usize, String, bool)> = manager
+        .get_subtasks(parent_id)
+        .unwrap()
+        .into_iter()
+        .map(|t| (t.id, t.text, t.completed))
+        .collect();
+    assert_eq!(labels, expected);
+    assert_eq!(
+        labels,
+        vec![
+            (subtask1_id, "Subtask 1".to_string(), false),
+            (subtask2_id, "Subtask 2".to_string(), true),
+        ]
+    );
+}
+
+#[test]
+fn test_focus_session_freezes_active_set() {
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), true).unwrap();
+
+    let focused: HashSet<usize> = manager.begin_focus().iter().map(|t| t.id).collect();
+    assert_eq!(focused, HashSet::from([task_a, task_b]));
+
+    manager.complete_task(task_b).unwrap();
+
+    let focus_ids: HashSet<usize> = manager.focus_set().unwrap().iter().map(|t| t.id).collect();
+    assert_eq!(focus_ids, focused);
+    let active_ids: HashSet<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active_ids, focused);
+
+    manager.end_focus();
+    assert!(manager.focus_set().is_none());
+    let active_ids: HashSet<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active_ids, HashSet::from([task_a]));
+}
+
+#[test]
+fn test_import_outline() {
+    let manager = TaskManager::new();
+    let outline = "Trip\n  Book flights\n  [x] Renew passport\n\tPack\n    Clothes\nGroceries\n";
+
+    let roots = manager.import_outline(outline, None).unwrap();
+    assert_eq!(roots.len(), 2);
+
+    let trip = manager.get_task(roots[0]).unwrap();
+    assert_eq!(trip.text, "Trip");
+    assert_eq!(trip.parent, None);
+
+    let children = manager.get_subtasks(roots[0]).unwrap();
+    let texts: Vec<&str> = children.iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(texts, vec!["Book flights", "Renew passport", "Pack"]);
+    let completed: Vec<bool> = children.iter().map(|t| t.completed).collect();
+    assert_eq!(completed, vec![false, true, false]);
+
+    let pack_children = manager.get_subtasks(children[2].id).unwrap();
+    assert_eq!(pack_children.len(), 1);
+    assert_eq!(pack_children[0].text, "Clothes");
+
+    let groceries = manager.get_task(roots[1]).unwrap();
+    assert_eq!(groceries.text, "Groceries");
+    assert!(groceries.subtasks.is_empty());
+}
+
+#[test]
+fn test_promote_to_root() {
+    let manager = TaskManager::new();
+    let parent_id = manager.add_task("Parent Task".to_string(), true).unwrap();
+    let first = manager.add_subtask(parent_id, "First".to_string()).unwrap();
+    let middle = manager
+        .add_subtask(parent_id, "Middle".to_string())
+        .unwrap();
+    let last = manager.add_subtask(parent_id, "Last".to_string()).unwrap();
+    let nested = manager.add_subtask(middle, "Nested".to_string()).unwrap();
+
+    manager.promote_to_root(middle).unwrap();
+
+    let promoted = manager.get_task(middle).unwrap();
+    assert_eq!(promoted.parent, None);
+    assert_eq!(promoted.subtasks, vec![nested]);
+    assert!(manager.root_tasks.lock().unwrap().contains(&middle));
+
+    let parent_task = manager.get_task(parent_id).unwrap();
+    assert_eq!(parent_task.subtasks, vec![first, last]);
+
+    // The ordered chain now goes straight from First to Last
+    manager.complete_task(first).unwrap();
+    let active_ids: HashSet<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active_ids, HashSet::from([last, nested]));
+
+    assert!(manager.promote_to_root(middle).is_err());
+}
+
+#[test]
+fn test_get_active_tasks_for_root() {
+    let manager = TaskManager::new();
+    let project_a = manager.add_task("Project A".to_string(), false).unwrap();
+    let project_b = manager.add_task("Project B".to_string(), false).unwrap();
+    let a1 = manager.add_subtask(project_a, "A1".to_string()).unwrap();
+    let a2 = manager.add_subtask(project_a, "A2".to_string()).unwrap();
+    let b1 = manager.add_subtask(project_b, "B1".to_string()).unwrap();
+
+    // A2 is blocked by B1 in the other project
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let a2_arc = tasks.get(&a2).unwrap().clone();
+        a2_arc.lock().unwrap().add_dependency(b1, true);
+    }
+
+    let ids: Vec<usize> = manager
+        .get_active_tasks_for_root(project_a)
+        .unwrap()
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(ids, vec![a1]);
+
+    let ids: Vec<usize> = manager
+        .get_active_tasks_for_root(project_b)
+        .unwrap()
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(ids, vec![b1]);
+
+    manager.complete_task(b1).unwrap();
+    let ids: Vec<usize> = manager
+        .get_active_tasks_for_root(project_a)
+        .unwrap()
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(ids, vec![a1, a2]);
+
+    assert!(manager.get_active_tasks_for_root(999).is_err());
+}
+
+#[test]
+fn test_waiting_tasks_leave_active_list() {
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), true).unwrap();
+
+    manager
+        .set_waiting(task_b, Some("Reply from Alex".to_string()))
+        .unwrap();
+
+    let active_ids: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active_ids, vec![task_a]);
+    let waiting = manager.get_waiting_tasks();
+    assert_eq!(waiting.len(), 1);
+    assert_eq!(waiting[0].id, task_b);
+    assert_eq!(waiting[0].waiting.as_deref(), Some("Reply from Alex"));
+
+    manager.set_waiting(task_b, None).unwrap();
+    assert!(manager.get_waiting_tasks().is_empty());
+    assert_eq!(manager.get_active_tasks().len(), 2);
+}
+
+#[test]
+fn test_get_recently_completed() {
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), true).unwrap();
+    let task_c = manager.add_task("Task C".to_string(), true).unwrap();
+    let task_d = manager.add_task("Task D".to_string(), true).unwrap();
+
+    manager.complete_task_at(task_b, 100).unwrap();
+    manager.complete_task_at(task_a, 300).unwrap();
+    manager.complete_task_at(task_c, 200).unwrap();
+
+    let ids: Vec<usize> = manager
+        .get_recently_completed(2)
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(ids, vec![task_a, task_c]);
+
+    manager.uncomplete_task(task_a, false).unwrap();
+    assert_eq!(manager.get_task(task_a).unwrap().completed_at, None);
+    let ids: Vec<usize> = manager
+        .get_recently_completed(10)
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(ids, vec![task_c, task_b]);
+    assert!(!ids.contains(&task_d));
+}
+
+#[test]
+fn test_apply_reorders_is_atomic() {
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), true).unwrap();
+    let a1 = manager.add_subtask(task_a, "A1".to_string()).unwrap();
+    let a2 = manager.add_subtask(task_a, "A2".to_string()).unwrap();
+    let b1 = manager.add_subtask(task_b, "B1".to_string()).unwrap();
+    let b2 = manager.add_subtask(task_b, "B2".to_string()).unwrap();
+
+    // The second pair is missing B1, so neither list may change
+    let result = manager.apply_reorders(vec![(task_a, vec![a2, a1]), (task_b, vec![b2])]);
+    assert!(result.is_err());
+    assert_eq!(manager.get_task(task_a).unwrap().subtasks, vec![a1, a2]);
+    assert_eq!(manager.get_task(task_b).unwrap().subtasks, vec![b1, b2]);
+
+    manager
+        .apply_reorders(vec![(task_a, vec![a2, a1]), (task_b, vec![b2, b1])])
+        .unwrap();
+    assert_eq!(manager.get_task(task_a).unwrap().subtasks, vec![a2, a1]);
+    assert_eq!(manager.get_task(task_b).unwrap().subtasks, vec![b2, b1]);
+}
+
+#[test]
+fn test_get_tasks_by_ids() {
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), true).unwrap();
+
+    let results = manager.get_tasks(vec![task_b, 999, task_a]);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().id, task_b);
+    assert!(results[1].is_none());
+    assert_eq!(results[2].as_ref().unwrap().id, task_a);
+}
+
+#[test]
+fn test_export_active_plaintext() {
+    let manager = TaskManager::new();
+    let work = manager.add_task("Work".to_string(), true).unwrap();
+    let home = manager.add_task("Home".to_string(), false).unwrap();
+    let report = manager.add_subtask(work, "Report".to_string()).unwrap();
+    manager.add_subtask(report, "Draft".to_string()).unwrap();
+    manager.add_subtask(home, "Dishes".to_string()).unwrap();
+    manager.add_subtask(home, "Laundry".to_string()).unwrap();
+
+    let text = manager.export_active_plaintext();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        lines,
+        vec!["[Work] Draft", "[Home] Dishes", "[Home] Laundry"]
+    );
+}
+
+#[test]
+fn test_filter_tasks_combines_constraints() {
+    use crate::core::task_manager::TaskFilter;
+
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), true).unwrap();
+    let task_c = manager.add_task("Task C".to_string(), true).unwrap();
+
+    manager.set_priority(task_a, 3).unwrap();
+    manager.add_tag(task_a, "work".to_string()).unwrap();
+    manager.set_priority(task_b, 3).unwrap();
+    manager.add_tag(task_c, "work".to_string()).unwrap();
+
+    let filter = TaskFilter {
+        min_priority: Some(2),
+        has_tag: Some("work".to_string()),
+        ..Default::default()
+    };
+    let ids: Vec<usize> = manager.filter_tasks(filter).iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![task_a]);
+
+    let ids: Vec<usize> = manager
+        .filter_tasks(TaskFilter::default())
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(ids, vec![task_a, task_b, task_c]);
+}
+
+#[test]
+fn test_get_active_tasks_dedupes_shared_subtasks() {
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), false).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), false).unwrap();
+    let shared = manager.add_subtask(task_a, "Shared".to_string()).unwrap();
+
+    // Link the same child under a second parent
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let task_b_arc = tasks.get(&task_b).unwrap().clone();
+        task_b_arc.lock().unwrap().subtasks.push(shared);
+    }
+
+    let active_ids: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active_ids, vec![shared]);
+}
+
+#[test]
+fn test_rename_tag() {
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), true).unwrap();
+    let task_c = manager.add_task("Task C".to_string(), true).unwrap();
+
+    manager.add_tag(task_a, "urgnet".to_string()).unwrap();
+    manager.add_tag(task_b, "urgent".to_string()).unwrap();
+    manager.add_tag(task_b, "urgnet".to_string()).unwrap();
+    manager.add_tag(task_c, "home".to_string()).unwrap();
+
+    assert_eq!(manager.rename_tag("urgnet", "urgent"), 2);
+    assert_eq!(manager.get_task(task_a).unwrap().tags, vec!["urgent"]);
+    assert_eq!(manager.get_task(task_b).unwrap().tags, vec!["urgent"]);
+    assert_eq!(manager.get_task(task_c).unwrap().tags, vec!["home"]);
+    assert_eq!(manager.rename_tag("urgnet", "urgent"), 0);
+
+    assert_eq!(manager.rename_tag("home", "home"), 0);
+    assert_eq!(manager.get_task(task_c).unwrap().tags, vec!["home"]);
+}
+
+#[test]
+fn test_get_active_tasks_page() {
+    let manager = TaskManager::new();
+    let ids: Vec<usize> = (0..5)
+        .map(|i| manager.add_task(format!("Task {}", i), true).unwrap())
+        .collect();
+
+    let page_ids = |offset, limit| {
+        let (page, total) = manager.get_active_tasks_page(offset, limit);
+        (page.iter().map(|t| t.id).collect::<Vec<usize>>(), total)
+    };
+
+    assert_eq!(page_ids(0, 2), (vec![ids[0], ids[1]], 5));
+    assert_eq!(page_ids(2, 2), (vec![ids[2], ids[3]], 5));
+    assert_eq!(page_ids(4, 10), (vec![ids[4]], 5));
+    assert_eq!(page_ids(10, 2), (vec![], 5));
+    assert_eq!(page_ids(0, 0), (vec![], 5));
+}
+
+#[test]
+fn test_export_graph() {
+    use crate::core::task_manager::{EdgeKind, GraphEdge};
+
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let a1 = manager.add_subtask(task_a, "A1".to_string()).unwrap();
+    let a2 = manager.add_subtask(task_a, "A2".to_string()).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), false).unwrap();
+    let b1 = manager.add_subtask(task_b, "B1".to_string()).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let b1_arc = tasks.get(&b1).unwrap().clone();
+        b1_arc.lock().unwrap().add_dependency(a2, true);
+    }
+
+    let graph = manager.export_graph();
+    let node_ids: Vec<usize> = graph.nodes.iter().map(|n| n.0).collect();
+    assert_eq!(node_ids, vec![task_a, a1, a2, task_b, b1]);
+
+    let edges: HashSet<GraphEdge> = graph.edges.into_iter().collect();
+    let edge = |from, to, kind| GraphEdge { from, to, kind };
+    let expected = HashSet::from([
+        edge(task_a, a1, EdgeKind::Parent),
+        edge(task_a, a2, EdgeKind::Parent),
+        edge(a1, a2, EdgeKind::Ordered),
+        edge(task_b, b1, EdgeKind::Parent),
+        edge(a2, b1, EdgeKind::Predecessor),
+    ]);
+    assert_eq!(edges, expected);
+}
+
+#[test]
+fn test_toggle_ordered_with_dangling_subtask() {
+    let manager = TaskManager::new();
+    let parent_id = manager.add_task("Parent Task".to_string(), true).unwrap();
+    let subtask_id = manager
+        .add_subtask(parent_id, "Subtask".to_string())
+        .unwrap();
+
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let parent_arc = tasks.get(&parent_id).unwrap().clone();
+        parent_arc.lock().unwrap().subtasks.push(999);
+    }
+
+    assert_eq!(manager.toggle_ordered(parent_id).unwrap(), 1);
+    assert!(!manager.get_task(parent_id).unwrap().ordered);
+    assert_eq!(manager.toggle_ordered(subtask_id).unwrap(), 0);
+}
+
+#[test]
+fn test_export_subtree_json_round_trip() {
+    let manager = TaskManager::new();
+    let project = manager.add_task("Project".to_string(), true).unwrap();
+    let step1 = manager.add_subtask(project, "Step 1".to_string()).unwrap();
+    let step2 = manager.add_subtask(project, "Step 2".to_string()).unwrap();
+    let detail = manager.add_subtask(step2, "Detail".to_string()).unwrap();
+    let unrelated = manager.add_task("Unrelated".to_string(), true).unwrap();
+    manager.complete_task(step1).unwrap();
+    manager.set_dependency(detail, step1, true).unwrap();
+    manager.set_dependency(detail, unrelated, true).unwrap();
+    manager.set_dependency(step1, unrelated, false).unwrap();
+
+    let json = manager.export_subtree_json(project).unwrap();
+    let path = temp_path("subtree_export_test.json");
+    std::fs::write(&path, json).unwrap();
+
+    let imported = TaskManager::new();
+    imported.load_from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(imported.tasks.lock().unwrap().len(), 4);
+    assert_eq!(*imported.root_tasks.lock().unwrap(), vec![project]);
+    assert_eq!(
+        imported.get_task(project).unwrap().subtasks,
+        vec![step1, step2]
+    );
+    assert!(imported.get_task(step1).unwrap().completed);
+    assert_eq!(imported.get_task(step2).unwrap().subtasks, vec![detail]);
+    assert_eq!(imported.get_task(detail).unwrap().parent, Some(step2));
+
+    // Only dependencies inside the subtree survive, so nothing needed repairing
+    assert_eq!(
+        imported.get_task(detail).unwrap().predecessors,
+        vec![Dependency {
+            id: step1,
+            hard: true
+        }]
+    );
+    assert!(imported.get_task(step1).unwrap().predecessors.is_empty());
+    assert!(imported.load_repairs().is_empty());
+}
+
+#[test]
+fn test_ephemeral_manager_does_not_write() {
+    let manager = TaskManager::new_ephemeral();
+    manager.add_task("Scratch".to_string(), true).unwrap();
+
+    let path = temp_path("ephemeral_test.json");
+    let _ = std::fs::remove_file(&path);
+
+    assert!(manager.is_ephemeral());
+    assert!(manager.save_to_file(path.to_str().unwrap()).is_ok());
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_get_ancestor_ids() {
+    let manager = TaskManager::new();
+    let root = manager.add_task("Root".to_string(), true).unwrap();
+    let child = manager.add_subtask(root, "Child".to_string()).unwrap();
+    let grandchild = manager
+        .add_subtask(child, "Grandchild".to_string())
+        .unwrap();
+
+    assert_eq!(
+        manager.get_ancestor_ids(grandchild).unwrap(),
+        vec![grandchild, child, root]
+    );
+    assert_eq!(manager.get_ancestor_ids(root).unwrap(), vec![root]);
+
+    // A parent cycle must not loop forever
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let root_arc = tasks.get(&root).unwrap().clone();
+        root_arc.lock().unwrap().parent = Some(grandchild);
+    }
+    assert!(manager.get_ancestor_ids(child).is_err());
+}
+
+#[test]
+fn test_reorder_move() {
+    let manager = TaskManager::new();
+    let parent_id = manager.add_task("Parent Task".to_string(), true).unwrap();
+    let first = manager.add_subtask(parent_id, "First".to_string()).unwrap();
+    let second = manager
+        .add_subtask(parent_id, "Second".to_string())
+        .unwrap();
+    let third = manager.add_subtask(parent_id, "Third".to_string()).unwrap();
+
+    manager.reorder_move(Some(parent_id), first, 2).unwrap();
+    assert_eq!(
+        manager.get_task(parent_id).unwrap().subtasks,
+        vec![second, third, first]
+    );
+
+    // Ordered activation follows the new position
+    let active_ids: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active_ids, vec![second]);
+
+    let other_root = manager.add_task("Other".to_string(), true).unwrap();
+    manager.reorder_move(None, other_root, 0).unwrap();
+    assert_eq!(
+        *manager.root_tasks.lock().unwrap(),
+        vec![other_root, parent_id]
+    );
+
+    assert!(manager
+        .reorder_move(Some(parent_id), other_root, 0)
+        .is_err());
+}
+
+#[test]
+fn test_attachments_round_trip() {
+    let manager = TaskManager::new();
+    let task_id = manager.add_task("Write spec".to_string(), true).unwrap();
+
+    manager
+        .add_attachment(task_id, "/docs/spec.pdf".to_string())
+        .unwrap();
+    manager
+        .add_attachment(task_id, "https://example.com/mockup".to_string())
+        .unwrap();
+    manager
+        .add_attachment(task_id, "/docs/spec.pdf".to_string())
+        .unwrap();
+    assert!(manager.add_attachment(task_id, "  ".to_string()).is_err());
+    manager
+        .remove_attachment(task_id, "/docs/spec.pdf")
+        .unwrap();
+
+    let path = temp_path("attachments_test.json");
+    manager.save_to_file(path.to_str().unwrap()).unwrap();
+    let reloaded = TaskManager::new();
+    reloaded.load_from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        reloaded.get_task(task_id).unwrap().attachments,
+        vec!["https://example.com/mockup"]
+    );
+}
+
+#[test]
+fn test_would_complete_project() {
+    let manager = TaskManager::new();
+    let almost_done = manager.add_task("Almost done".to_string(), true).unwrap();
+    let step1 = manager
+        .add_subtask(almost_done, "Step 1".to_string())
+        .unwrap();
+    let step2 = manager
+        .add_subtask(almost_done, "Step 2".to_string())
+        .unwrap();
+    manager.complete_task(step1).unwrap();
+
+    let far_off = manager.add_task("Far off".to_string(), false).unwrap();
+    let item1 = manager.add_subtask(far_off, "Item 1".to_string()).unwrap();
+    manager.add_subtask(far_off, "Item 2".to_string()).unwrap();
+
+    assert_eq!(
+        manager.would_complete_project(step2).unwrap(),
+        Some(almost_done)
+    );
+    assert_eq!(manager.would_complete_project(item1).unwrap(), None);
+
+    // Nothing is actually completed by the check
+    assert!(!manager.get_task(step2).unwrap().completed);
+    assert!(manager.would_complete_project(999).is_err());
+}
+
+#[test]
+fn test_quick_add_parses_inline_metadata() {
+    use crate::core::task_manager::parse_date;
+
+    let manager = TaskManager::new();
+    let id = manager
+        .quick_add("Buy milk !2 #errand @2024-06-01", None)
+        .unwrap();
+
+    let task = manager.get_task(id).unwrap();
+    assert_eq!(task.text, "Buy milk");
+    assert_eq!(task.priority, 2);
+    assert_eq!(task.tags, vec!["errand"]);
+    assert_eq!(task.due_date, Some(1_717_200_000));
+    assert_eq!(parse_date("2024-06-01"), Some(1_717_200_000));
+    assert_eq!(parse_date("1970-01-01"), Some(0));
+    assert_eq!(parse_date("2024-02-29"), Some(1_709_164_800));
+    assert_eq!(parse_date("2024-02-31"), None);
+    assert_eq!(parse_date("2023-02-29"), None);
+    assert_eq!(parse_date("2024-04-31"), None);
+
+    // An impossible date is not metadata, so it stays in the text
+    let id = manager.quick_add("Pay rent @2024-02-31", None).unwrap();
+    let task = manager.get_task(id).unwrap();
+    assert_eq!(task.text, "Pay rent @2024-02-31");
+    assert_eq!(task.due_date, None);
+
+    assert!(manager.quick_add("#only-a-tag", None).is_err());
+}
+
+#[test]
+fn test_max_text_length_counts_characters() {
+    let manager = TaskManager::new();
+    manager.set_max_text_length(5);
+
+    // Five characters but more than five bytes
+    let id = manager.add_task("héllo".to_string(), true).unwrap();
+    manager.add_subtask(id, "日本語です".to_string()).unwrap();
+    manager
+        .update_task_text(id, "🦀🦀🦀🦀🦀".to_string())
+        .unwrap();
+
+    assert!(manager.add_task("héllo!".to_string(), true).is_err());
+    assert!(manager.add_subtask(id, "日本語ですね".to_string()).is_err());
+    assert!(manager
+        .update_task_text(id, "🦀🦀🦀🦀🦀🦀".to_string())
+        .is_err());
+    assert_eq!(manager.get_task(id).unwrap().text, "🦀🦀🦀🦀🦀");
+}
+
+#[test]
+fn test_get_agenda_groups_by_day() {
+    const DAY: i64 = 86_400;
+    let manager = TaskManager::new();
+    let morning = manager.add_task("Morning".to_string(), true).unwrap();
+    let evening = manager.add_task("Evening".to_string(), true).unwrap();
+    let next_day = manager.add_task("Next day".to_string(), true).unwrap();
+    let too_late = manager.add_task("Too late".to_string(), true).unwrap();
+    let done = manager.add_task("Done".to_string(), true).unwrap();
+
+    manager
+        .set_due_date(evening, Some(10 * DAY + 20 * 3600))
+        .unwrap();
+    manager
+        .set_due_date(morning, Some(10 * DAY + 8 * 3600))
+        .unwrap();
+    manager
+        .set_due_date(next_day, Some(11 * DAY + 3600))
+        .unwrap();
+    manager.set_due_date(too_late, Some(12 * DAY)).unwrap();
+    manager.set_due_date(done, Some(10 * DAY)).unwrap();
+    manager.complete_task(done).unwrap();
+
+    let agenda: Vec<(i64, Vec<usize>)> = manager
+        .get_agenda(10 * DAY, 12 * DAY)
+        .into_iter()
+        .map(|(day, tasks)| (day, tasks.iter().map(|t| t.id).collect()))
+        .collect();
+    assert_eq!(
+        agenda,
+        vec![
+            (10 * DAY, vec![morning, evening]),
+            (11 * DAY, vec![next_day])
+        ]
+    );
+}
+
+#[test]
+fn test_save_to_file_creates_missing_directories() {
+    let manager = TaskManager::new();
+    manager.add_task("Task".to_string(), true).unwrap();
+
+    let base_dir = temp_path("nested_save_test");
+    let _ = std::fs::remove_dir_all(&base_dir);
+    let path = base_dir.join("a").join("b").join("data.json");
+
+    manager.save_to_file(path.to_str().unwrap()).unwrap();
+    assert!(path.parent().unwrap().is_dir());
+    assert!(path.is_file());
+
+    std::fs::remove_dir_all(&base_dir).unwrap();
+}
+
+#[test]
+fn test_get_siblings() {
+    let manager = TaskManager::new();
+    let parent_id = manager.add_task("Parent Task".to_string(), true).unwrap();
+    let first = manager.add_subtask(parent_id, "First".to_string()).unwrap();
+    let middle = manager
+        .add_subtask(parent_id, "Middle".to_string())
+        .unwrap();
+    let last = manager.add_subtask(parent_id, "Last".to_string()).unwrap();
+    let other_root = manager.add_task("Other".to_string(), true).unwrap();
+
+    let ids: Vec<usize> = manager
+        .get_siblings(middle)
+        .unwrap()
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(ids, vec![first, last]);
+
+    let ids: Vec<usize> = manager
+        .get_siblings(parent_id)
+        .unwrap()
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(ids, vec![other_root]);
+}
+
+#[test]
+fn test_in_progress_lifecycle() {
+    let manager = TaskManager::new();
+    let todo = manager.add_task("Todo".to_string(), true).unwrap();
+    let doing = manager.add_task("Doing".to_string(), true).unwrap();
+    let done = manager.add_task("Done".to_string(), true).unwrap();
+    manager.complete_task(done).unwrap();
+
+    manager.start_task(doing).unwrap();
+    assert!(manager.start_task(done).is_err());
+
+    let ids: Vec<usize> = manager
+        .get_in_progress_tasks()
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(ids, vec![doing]);
+
+    let stats = manager.get_stats();
+    assert_eq!(stats.total, 3);
+    assert_eq!(stats.todo, 1);
+    assert_eq!(stats.in_progress, 1);
+    assert_eq!(stats.completed, 1);
+
+    // Completing clears the in-progress flag
+    manager.complete_task(doing).unwrap();
+    assert!(manager.get_in_progress_tasks().is_empty());
+    assert!(!manager.get_task(doing).unwrap().in_progress);
+    assert!(!manager.get_task(todo).unwrap().in_progress);
+}
+
+#[test]
+fn test_upsert_task_persists_change() {
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), true).unwrap();
+
+    let path = temp_path("upsert_test.json");
+    manager.save_to_file(path.to_str().unwrap()).unwrap();
+    assert!(!*manager.dirty.lock().unwrap());
+
+    manager
+        .update_task_text(task_b, "Task B (renamed)".to_string())
+        .unwrap();
+    manager.upsert_task(task_b).unwrap();
+    assert!(*manager.dirty.lock().unwrap());
+    manager.save_to_file(path.to_str().unwrap()).unwrap();
+
+    let reloaded = TaskManager::new();
+    reloaded.load_from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(reloaded.get_task(task_b).unwrap().text, "Task B (renamed)");
+    assert_eq!(reloaded.get_task(task_a).unwrap().text, "Task A");
+    assert!(manager.upsert_task(999).is_err());
+}
+
+#[test]
+fn test_reorder_subtasks_reports_no_op() {
+    let manager = TaskManager::new();
+    let parent_id = manager.add_task("Parent Task".to_string(), true).unwrap();
+    let first = manager.add_subtask(parent_id, "First".to_string()).unwrap();
+    let second = manager
+        .add_subtask(parent_id, "Second".to_string())
+        .unwrap();
+    *manager.dirty.lock().unwrap() = false;
+
+    assert!(!manager
+        .reorder_subtasks(parent_id, vec![first, second])
+        .unwrap());
+    assert!(!*manager.dirty.lock().unwrap());
+
+    assert!(manager
+        .reorder_subtasks(parent_id, vec![second, first])
+        .unwrap());
+    assert!(*manager.dirty.lock().unwrap());
+}
+
+#[test]
+fn test_get_orphans() {
+    let manager = TaskManager::new();
+    let parent_id = manager.add_task("Parent Task".to_string(), true).unwrap();
+    let subtask_id = manager
+        .add_subtask(parent_id, "Subtask".to_string())
+        .unwrap();
+    let lost_root = manager.add_task("Lost root".to_string(), true).unwrap();
+    assert!(manager.get_orphans().is_empty());
+
+    // Detach one subtask from its parent and drop a root from the root list
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let parent_arc = tasks.get(&parent_id).unwrap().clone();
+        parent_arc.lock().unwrap().subtasks.clear();
+    }
+    manager
+        .root_tasks
+        .lock()
+        .unwrap()
+        .retain(|&id| id != lost_root);
+
+    let ids: Vec<usize> = manager.get_orphans().iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![subtask_id, lost_root]);
+}
+
+#[test]
+fn test_task_serializes_camel_case_but_file_stays_snake_case() {
+    let manager = TaskManager::new_ephemeral();
+    let id = manager.add_task("Ship".to_string(), false).unwrap();
+    manager.set_due_date(id, Some(1_700_000_000)).unwrap();
+
+    let task = manager.get_task(id).unwrap();
+    let command_json = serde_json::to_value(&task).unwrap();
+    assert!(command_json.get("dueDate").is_some());
+    assert!(command_json.get("due_date").is_none());
+
+    let file_json = manager.export_subtree_json(id).unwrap();
+    assert!(file_json.contains("\"due_date\""));
+    assert!(!file_json.contains("\"dueDate\""));
+
+    let path = temp_path("snake_case_test.json");
+    std::fs::write(&path, file_json).unwrap();
+    let reloaded = TaskManager::new_ephemeral();
+    reloaded.load_from_file(path.to_str().unwrap()).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(reloaded.get_task(id).unwrap().due_date, Some(1_700_000_000));
+}
+
+#[test]
+fn test_critical_path_follows_longest_chain() {
+    let manager = TaskManager::new();
+    let project = manager.add_task("Project".to_string(), false).unwrap();
+    manager.add_subtask(project, "Design".to_string()).unwrap();
+    let build = manager.add_subtask(project, "Build".to_string()).unwrap();
+    let docs = manager.add_subtask(project, "Docs".to_string()).unwrap();
+    let step1 = manager.add_subtask(build, "Step 1".to_string()).unwrap();
+    let step2 = manager.add_subtask(build, "Step 2".to_string()).unwrap();
+    let step3 = manager.add_subtask(build, "Step 3".to_string()).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&docs].lock().unwrap().add_dependency(step3, true);
+    }
+
+    assert_eq!(
+        manager.critical_path(project).unwrap(),
+        vec![step1, step2, step3, docs]
+    );
+
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&step1].lock().unwrap().add_dependency(docs, true);
+    }
+    assert!(manager.critical_path(project).is_err());
+    assert!(manager.critical_path(999).is_err());
+}
+
+#[test]
+fn test_uncomplete_reopens_only_auto_completed_ancestors() {
+    let manager = TaskManager::new();
+    manager.set_auto_complete_parents(true);
+    let root = manager.add_task("Root".to_string(), false).unwrap();
+    let middle = manager.add_subtask(root, "Middle".to_string()).unwrap();
+    let leaf = manager.add_subtask(middle, "Leaf".to_string()).unwrap();
+
+    manager.complete_task(root).unwrap();
+    manager.complete_task(leaf).unwrap();
+    assert!(manager.get_task(middle).unwrap().completed);
+    assert!(manager.get_task(middle).unwrap().auto_completed);
+
+    manager.uncomplete_task(leaf, true).unwrap();
+    assert!(!manager.get_task(leaf).unwrap().completed);
+    assert!(!manager.get_task(middle).unwrap().completed);
+    // Root was closed by hand, so it stays completed
+    assert!(manager.get_task(root).unwrap().completed);
+
+    manager.complete_task(leaf).unwrap();
+    manager.uncomplete_task(leaf, false).unwrap();
+    assert!(manager.get_task(middle).unwrap().completed);
+}
+
+#[test]
+fn test_export_state_json_round_trips() {
+    let manager = TaskManager::new();
+    let root = manager.add_task("Root".to_string(), true).unwrap();
+    manager.add_subtask(root, "Child".to_string()).unwrap();
+
+    let json = manager.export_state_json().unwrap();
+    let data: TaskManagerData = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(data.root_tasks, vec![root]);
+    assert_eq!(data.next_id, *manager.next_id.lock().unwrap());
+    let texts: Vec<&str> = data.tasks.iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(texts, vec!["Root", "Child"]);
+}
+
+#[test]
+fn test_remove_task_scrubs_predecessors() {
+    let manager = TaskManager::new();
+    let x = manager.add_task("X".to_string(), false).unwrap();
+    let y = manager.add_task("Y".to_string(), false).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&x].lock().unwrap().add_dependency(y, true);
+    }
+    assert!(!manager.get_active_tasks().iter().any(|t| t.id == x));
+
+    manager.remove_task_recursive(y).unwrap();
+
+    assert!(manager.get_task(x).unwrap().predecessors.is_empty());
+    assert!(manager.get_active_tasks().iter().any(|t| t.id == x));
+}
+
+#[test]
+fn test_preview_weekly_occurrences() {
+    use crate::core::task_manager::{parse_date, Recurrence};
+
+    let manager = TaskManager::new();
+    let id = manager.add_task("Review".to_string(), false).unwrap();
+    assert!(manager.preview_occurrences(id, 3).is_err());
+
+    let due = parse_date("2024-01-01").unwrap();
+    manager.set_due_date(id, Some(due)).unwrap();
+    manager
+        .set_recurrence(id, Some(Recurrence::Weekly))
+        .unwrap();
+
+    let dates = manager.preview_occurrences(id, 3).unwrap();
+    assert_eq!(dates.len(), 3);
+    assert_eq!(dates[0], due + 7 * 86_400);
+    for pair in dates.windows(2) {
+        assert_eq!(pair[1] - pair[0], 7 * 86_400);
+    }
+    assert!(manager
+        .set_recurrence(id, Some(Recurrence::EveryDays(0)))
+        .is_err());
+}
+
+#[test]
+fn test_find_cycles_reports_mutual_blockers() {
+    let manager = TaskManager::new();
+    let a = manager.add_task("A".to_string(), false).unwrap();
+    let b = manager.add_task("B".to_string(), false).unwrap();
+    let c = manager.add_task("C".to_string(), false).unwrap();
+    assert!(manager.find_cycles().is_empty());
+
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&a].lock().unwrap().add_dependency(b, true);
+        tasks[&b].lock().unwrap().add_dependency(a, true);
+        tasks[&c].lock().unwrap().add_dependency(a, true);
+    }
+
+    assert_eq!(manager.find_cycles(), vec![vec![a, b]]);
+
+    // A long dependency loop is walked without recursion
+    let chain: Vec<usize> = (0..50_000)
+        .map(|i| manager.add_task(format!("Link {}", i), false).unwrap())
+        .collect();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        for pair in chain.windows(2) {
+            tasks[&pair[1]]
+                .lock()
+                .unwrap()
+                .add_dependency(pair[0], true);
+        }
+        tasks[&chain[0]]
+            .lock()
+            .unwrap()
+            .add_dependency(chain[chain.len() - 1], true);
+    }
+    assert_eq!(manager.find_cycles(), vec![vec![a, b], chain]);
+}
+
+#[test]
+fn test_remove_tasks_skips_already_removed_descendants() {
+    let manager = TaskManager::new();
+    let root = manager.add_task("Root".to_string(), false).unwrap();
+    let child = manager.add_subtask(root, "Child".to_string()).unwrap();
+    let grandchild = manager
+        .add_subtask(child, "Grandchild".to_string())
+        .unwrap();
+    let other = manager.add_task("Other".to_string(), false).unwrap();
+
+    let mut removed = manager.remove_tasks(vec![root, child]).unwrap();
+    removed.sort();
+    assert_eq!(removed, vec![root, child, grandchild]);
+    assert_eq!(*manager.root_tasks.lock().unwrap(), vec![other]);
 
-        let subtask1 = manager.get_task(subtask1_id).unwrap();
-        let subtask2 = manager.get_task(subtask2_id).unwrap();
+    assert!(manager.remove_tasks(vec![other, 999]).is_err());
+    assert!(manager.get_task(other).is_some());
 
-        assert!(subtask1.predecessors.is_empty());
-        assert_eq!(subtask2.predecessors, vec![subtask1_id]);
+    // A descendant listed before its ancestor is covered by the ancestor's subtree
+    let project = manager.add_task("Project".to_string(), false).unwrap();
+    let step = manager.add_subtask(project, "Step".to_string()).unwrap();
+    let mut removed = manager.remove_tasks(vec![step, project, step]).unwrap();
+    removed.sort();
+    assert_eq!(removed, vec![project, step]);
+    assert_eq!(*manager.root_tasks.lock().unwrap(), vec![other]);
+}
+
+#[test]
+fn test_get_subtasks_sorted_incomplete_first() {
+    use crate::core::task_manager::SubtaskSort;
+
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Parent".to_string(), false).unwrap();
+    let a = manager.add_subtask(parent, "A".to_string()).unwrap();
+    let b = manager.add_subtask(parent, "B".to_string()).unwrap();
+    let c = manager.add_subtask(parent, "C".to_string()).unwrap();
+    let d = manager.add_subtask(parent, "D".to_string()).unwrap();
+    manager.complete_task(a).unwrap();
+    manager.complete_task(c).unwrap();
+
+    let ids = |sort| -> Vec<usize> {
+        manager
+            .get_subtasks_sorted(parent, sort)
+            .unwrap()
+            .iter()
+            .map(|t| t.id)
+            .collect()
+    };
+    assert_eq!(ids(SubtaskSort::Manual), vec![a, b, c, d]);
+    assert_eq!(ids(SubtaskSort::IncompleteFirst), vec![b, d, a, c]);
+}
+
+#[test]
+fn test_validate_reorder_warns_about_sibling_predecessor() {
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Parent".to_string(), true).unwrap();
+    let a = manager.add_subtask(parent, "A".to_string()).unwrap();
+    let b = manager.add_subtask(parent, "B".to_string()).unwrap();
+    let c = manager.add_subtask(parent, "C".to_string()).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&c].lock().unwrap().add_dependency(a, true);
+    }
+
+    assert!(manager
+        .validate_reorder(parent, vec![b, a, c])
+        .unwrap()
+        .is_empty());
+    let warnings = manager.validate_reorder(parent, vec![c, a, b]).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains(&c.to_string()));
+    assert!(manager.validate_reorder(parent, vec![a, b]).is_err());
+    // Nothing was applied
+    assert_eq!(
+        manager.tasks.lock().unwrap()[&parent]
+            .lock()
+            .unwrap()
+            .subtasks,
+        vec![a, b, c]
+    );
+}
+
+#[test]
+fn test_incomplete_counts_by_root() {
+    let manager = TaskManager::new();
+    let first = manager.add_task("First".to_string(), false).unwrap();
+    let a = manager.add_subtask(first, "A".to_string()).unwrap();
+    let b = manager.add_subtask(a, "B".to_string()).unwrap();
+    manager.add_subtask(first, "C".to_string()).unwrap();
+    let second = manager.add_task("Second".to_string(), false).unwrap();
+    let d = manager.add_subtask(second, "D".to_string()).unwrap();
+    manager.complete_task(b).unwrap();
+    manager.complete_task(d).unwrap();
+
+    let done = manager.add_task("Done".to_string(), false).unwrap();
+    manager.complete_task(done).unwrap();
+
+    // Roots count themselves while they are open
+    let counts = manager.incomplete_counts_by_root();
+    assert_eq!(counts.len(), 3);
+    assert_eq!(counts[&first], 3);
+    assert_eq!(counts[&second], 1);
+    assert_eq!(counts[&done], 0);
+}
+
+#[test]
+fn test_repeated_saves_are_byte_identical() {
+    let manager = TaskManager::new();
+    for i in 0..20 {
+        manager.add_task(format!("Task {}", i), false).unwrap();
+    }
+    let path = temp_path("stable_save_test.json");
+    manager.save_to_file(path.to_str().unwrap()).unwrap();
+    let first = std::fs::read(&path).unwrap();
+
+    let reloaded = TaskManager::new();
+    reloaded.load_from_file(path.to_str().unwrap()).unwrap();
+    reloaded.save_to_file(path.to_str().unwrap()).unwrap();
+    let second = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(first, second);
+    assert!(String::from_utf8(first).unwrap().contains('\n'));
+}
+
+#[test]
+fn test_delete_keep_children_moves_them_into_place() {
+    let manager = TaskManager::new();
+    let grandparent = manager.add_task("Grandparent".to_string(), true).unwrap();
+    let before = manager
+        .add_subtask(grandparent, "Before".to_string())
+        .unwrap();
+    let middle = manager
+        .add_subtask(grandparent, "Middle".to_string())
+        .unwrap();
+    let after = manager
+        .add_subtask(grandparent, "After".to_string())
+        .unwrap();
+    let first = manager.add_subtask(middle, "First".to_string()).unwrap();
+    let second = manager.add_subtask(middle, "Second".to_string()).unwrap();
+
+    manager.delete_keep_children(middle).unwrap();
+
+    assert!(manager.get_task(middle).is_none());
+    assert_eq!(
+        manager.get_task(grandparent).unwrap().subtasks,
+        vec![before, first, second, after]
+    );
+    assert_eq!(manager.get_task(first).unwrap().parent, Some(grandparent));
+    assert_eq!(manager.get_task(second).unwrap().parent, Some(grandparent));
+
+    manager.delete_keep_children(grandparent).unwrap();
+    assert_eq!(
+        *manager.root_tasks.lock().unwrap(),
+        vec![before, first, second, after]
+    );
+    assert_eq!(manager.get_task(first).unwrap().parent, None);
+}
+
+#[test]
+fn test_locked_task_rejects_edits_until_unlocked() {
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Reference".to_string(), true).unwrap();
+    let child = manager.add_subtask(parent, "Step".to_string()).unwrap();
+    let other = manager.add_subtask(parent, "Other".to_string()).unwrap();
+
+    manager.lock_task(parent).unwrap();
+    assert!(manager
+        .update_task_text(parent, "Changed".to_string())
+        .is_err());
+    assert!(manager.complete_task(parent).is_err());
+    assert!(manager
+        .reorder_subtasks(parent, vec![other, child])
+        .is_err());
+    assert!(manager.remove_task_recursive(parent).is_err());
+    assert_eq!(manager.get_task(parent).unwrap().text, "Reference");
+
+    manager.lock_task(child).unwrap();
+    manager.unlock_task(parent).unwrap();
+    // A locked descendant still protects the whole subtree
+    assert!(manager.remove_task_recursive(parent).is_err());
+    assert!(manager.get_task(other).is_some());
+
+    manager
+        .update_task_text(parent, "Changed".to_string())
+        .unwrap();
+    assert_eq!(manager.get_task(parent).unwrap().text, "Changed");
+}
+
+#[test]
+fn test_locked_task_rejects_reopening_and_reordering() {
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Reference".to_string(), true).unwrap();
+    let child = manager.add_subtask(parent, "Step".to_string()).unwrap();
+    let other = manager.add_task("Other".to_string(), false).unwrap();
+    manager.complete_task(child).unwrap();
+    manager.lock_task(child).unwrap();
+    manager.lock_task(parent).unwrap();
+
+    assert!(manager.uncomplete_task(child, false).is_err());
+    assert!(manager.get_task(child).unwrap().completed);
+    assert!(manager.toggle_ordered(parent).is_err());
+    assert!(manager.get_task(parent).unwrap().ordered);
+    assert!(manager.promote_to_root(child).is_err());
+    assert!(manager
+        .set_waiting(parent, Some("Review".to_string()))
+        .is_err());
+    assert!(manager.reset_subtree(parent).is_err());
+    assert!(manager.tag_subtree(parent, "ref".to_string()).is_err());
+    assert!(manager.set_priority_bulk(vec![other, parent], 3).is_err());
+    assert!(manager.merge_tasks(parent, other).is_err());
+
+    // Nothing was partially applied
+    assert!(manager.get_task(child).unwrap().completed);
+    assert_eq!(manager.get_task(child).unwrap().parent, Some(parent));
+    assert!(manager.get_task(parent).unwrap().waiting.is_none());
+    assert!(manager.get_task(parent).unwrap().tags.is_empty());
+    assert_eq!(manager.get_task(other).unwrap().priority, 0);
+    assert!(manager.get_task(other).is_some());
 
-        let parent_task = manager.get_task(parent_id).unwrap();
-        assert_eq!(parent_task.subtasks, vec![subtask1_id, subtask2_id]);
+    manager.unlock_task(child).unwrap();
+    manager.unlock_task(parent).unwrap();
+    manager.uncomplete_task(child, false).unwrap();
+    assert!(!manager.get_task(child).unwrap().completed);
+    assert_eq!(manager.toggle_ordered(parent).unwrap(), 0);
+    assert!(!manager.get_task(parent).unwrap().ordered);
+}
+
+#[test]
+fn test_estimate_finish_date_from_throughput() {
+    let manager = TaskManager::new();
+    let root = manager.add_task("Launch".to_string(), false).unwrap();
+    for i in 0..6 {
+        manager.add_subtask(root, format!("Step {}", i)).unwrap();
+    }
+    let done = manager.add_subtask(root, "Done".to_string()).unwrap();
+    manager.complete_task(done).unwrap();
+
+    let now = 1_700_000_000;
+    // Six open steps at two a day takes three days
+    assert_eq!(
+        manager.estimate_finish_date_from(root, 2.0, now).unwrap(),
+        now + 3 * 86_400
+    );
+
+    assert!(manager.estimate_finish_date_from(root, 0.0, now).is_err());
+    assert!(manager.estimate_finish_date_from(999, 1.0, now).is_err());
+}
+
+#[test]
+fn test_swap_tasks_across_parents() {
+    let manager = TaskManager::new();
+    let left = manager.add_task("Left".to_string(), true).unwrap();
+    let l1 = manager.add_subtask(left, "L1".to_string()).unwrap();
+    let l2 = manager.add_subtask(left, "L2".to_string()).unwrap();
+    let right = manager.add_task("Right".to_string(), true).unwrap();
+    let r1 = manager.add_subtask(right, "R1".to_string()).unwrap();
+
+    manager.swap_tasks(l2, r1).unwrap();
+
+    assert_eq!(manager.get_task(left).unwrap().subtasks, vec![l1, r1]);
+    assert_eq!(manager.get_task(right).unwrap().subtasks, vec![l2]);
+    assert_eq!(manager.get_task(r1).unwrap().parent, Some(left));
+    assert_eq!(manager.get_task(l2).unwrap().parent, Some(right));
+
+    assert!(manager.swap_tasks(left, l1).is_err());
+    assert!(manager.swap_tasks(r1, left).is_err());
+
+    // A locked task or a locked parent on either side blocks the swap
+    for locked in [l1, l2, left, right] {
+        manager.lock_task(locked).unwrap();
+        assert!(manager.swap_tasks(l1, l2).is_err());
+        manager.unlock_task(locked).unwrap();
     }
+    assert_eq!(manager.get_task(left).unwrap().subtasks, vec![l1, r1]);
+    assert_eq!(manager.get_task(right).unwrap().subtasks, vec![l2]);
+}
+
+#[test]
+fn test_manual_save_produces_loadable_file() {
+    let manager = TaskManager::new();
+    let root = manager.add_task("Important".to_string(), true).unwrap();
+    let child = manager
+        .add_subtask(root, "Just edited".to_string())
+        .unwrap();
+    manager.complete_task(child).unwrap();
+
+    let path = temp_path("save_now_test.json");
+    manager.save_to_file(path.to_str().unwrap()).unwrap();
+    assert!(!*manager.dirty.lock().unwrap());
+
+    let reloaded = TaskManager::new();
+    reloaded.load_from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(*reloaded.root_tasks.lock().unwrap(), vec![root]);
+    assert!(reloaded.get_task(child).unwrap().completed);
+    assert_eq!(reloaded.get_task(child).unwrap().text, "Just edited");
+}
+
+#[test]
+fn test_subtasks_inherit_parent_tags() {
+    let manager = TaskManager::new();
+    let root = manager.add_task("Project".to_string(), false).unwrap();
+    let child = manager.add_subtask(root, "Child".to_string()).unwrap();
+    let grandchild = manager
+        .add_subtask(child, "Grandchild".to_string())
+        .unwrap();
+    manager.add_tag(root, "work".to_string()).unwrap();
+    manager.add_tag(grandchild, "urgent".to_string()).unwrap();
+
+    assert!(manager.get_task(child).unwrap().tags.is_empty());
+    assert_eq!(manager.get_effective_tags(child).unwrap(), vec!["work"]);
+    assert_eq!(
+        manager.get_effective_tags(grandchild).unwrap(),
+        vec!["urgent", "work"]
+    );
+
+    let direct: Vec<usize> = manager
+        .get_tasks_by_tag("work", false)
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(direct, vec![root]);
+    let inherited: Vec<usize> = manager
+        .get_tasks_by_tag("work", true)
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(inherited, vec![root, child, grandchild]);
+}
+
+#[test]
+fn test_deepest_active_leaf_on_asymmetric_tree() {
+    let manager = TaskManager::new();
+    let root = manager.add_task("Root".to_string(), false).unwrap();
+    let shallow = manager.add_subtask(root, "Shallow".to_string()).unwrap();
+    let branch = manager.add_subtask(root, "Branch".to_string()).unwrap();
+    let inner = manager.add_subtask(branch, "Inner".to_string()).unwrap();
+    let first = manager.add_subtask(inner, "First".to_string()).unwrap();
+    manager.add_subtask(inner, "Second".to_string()).unwrap();
+    let other = manager.add_subtask(branch, "Other".to_string()).unwrap();
+    manager
+        .add_subtask(other, "Other leaf".to_string())
+        .unwrap();
 
-    #[test]
-    fn test_get_active_tasks_complex() {
-        let manager = TaskManager::new();
+    let leaf = manager.deepest_active_leaf(root).unwrap().unwrap();
+    assert_eq!(leaf.id, first);
 
-        // Create main tasks
-        let task_a = manager.add_task("Task A".to_string(), true); // Ordered
-        let task_b = manager.add_task("Task B".to_string(), false); // Unordered
-        let task_c = manager.add_task("Task C".to_string(), true); // Ordered
+    manager.complete_task(branch).unwrap();
+    let leaf = manager.deepest_active_leaf(root).unwrap().unwrap();
+    assert_eq!(leaf.id, shallow);
+    assert!(manager.deepest_active_leaf(999).is_err());
 
-        // Add subtasks to Task A
-        let task_a1 = manager.add_subtask(task_a, "Task A1".to_string()).unwrap();
-        let task_a2 = manager.add_subtask(task_a, "Task A2".to_string()).unwrap();
-        let task_a3 = manager.add_subtask(task_a, "Task A3".to_string()).unwrap();
+    // A deeper parent whose children are all done is active, but not a leaf
+    let nested = manager.add_subtask(root, "Nested".to_string()).unwrap();
+    let finished = manager.add_subtask(nested, "Finished".to_string()).unwrap();
+    let done = manager.add_subtask(finished, "Done".to_string()).unwrap();
+    manager.complete_task(done).unwrap();
+    assert!(manager
+        .get_active_tasks_for_root(root)
+        .unwrap()
+        .iter()
+        .any(|t| t.id == finished));
+    let leaf = manager.deepest_active_leaf(root).unwrap().unwrap();
+    assert_eq!(leaf.id, shallow);
 
-        // Add subtasks to Task B
-        let task_b1 = manager.add_subtask(task_b, "Task B1".to_string()).unwrap();
-        let task_b2 = manager.add_subtask(task_b, "Task B2".to_string()).unwrap();
+    let lone = manager.add_task("Lone".to_string(), false).unwrap();
+    let lone_done = manager.add_subtask(lone, "Done".to_string()).unwrap();
+    manager.complete_task(lone_done).unwrap();
+    assert!(manager.deepest_active_leaf(lone).unwrap().is_none());
+}
 
-        // Add subtasks to Task C
-        let task_c1 = manager.add_subtask(task_c, "Task C1".to_string()).unwrap();
-        let task_c2 = manager.add_subtask(task_c, "Task C2".to_string()).unwrap();
+#[test]
+fn test_concurrent_add_and_remove_leaves_no_orphans() {
+    use std::sync::Arc;
+    use std::thread;
 
-        // Add dependencies
-        // Task A3 depends on Task B2
-        {
-            let tasks = manager.tasks.lock().unwrap();
-            let task_a3_arc = tasks.get(&task_a3).unwrap().clone();
-            let mut task_a3_lock = task_a3_arc.lock().unwrap();
-            task_a3_lock.predecessors.push(task_b2);
+    let manager = Arc::new(TaskManager::new());
+    for _ in 0..200 {
+        let parent = manager.add_task("Parent".to_string(), false).unwrap();
+        for i in 0..5 {
+            manager
+                .add_subtask(parent, format!("Existing {}", i))
+                .unwrap();
         }
 
-        // Task B2 depends on Task C
-        {
-            let tasks = manager.tasks.lock().unwrap();
-            let task_b2_arc = tasks.get(&task_b2).unwrap().clone();
-            let mut task_b2_lock = task_b2_arc.lock().unwrap();
-            task_b2_lock.predecessors.push(task_c);
+        let adder = {
+            let manager = Arc::clone(&manager);
+            thread::spawn(move || {
+                for i in 0..20 {
+                    let _ = manager.add_subtask(parent, format!("New {}", i));
+                }
+            })
+        };
+        let remover = {
+            let manager = Arc::clone(&manager);
+            thread::spawn(move || manager.remove_task_recursive(parent).unwrap())
+        };
+        adder.join().unwrap();
+        remover.join().unwrap();
+    }
+
+    let tasks = manager.tasks.lock().unwrap();
+    for task in tasks.values() {
+        let task = task.lock().unwrap();
+        if let Some(parent) = task.parent {
+            assert!(tasks.contains_key(&parent), "task {} is orphaned", task.id);
         }
+    }
+}
+
+#[test]
+fn test_time_report_sums_sessions_in_window() {
+    let manager = TaskManager::new();
+    let writing = manager.add_task("Writing".to_string(), false).unwrap();
+    let review = manager.add_task("Review".to_string(), false).unwrap();
+    let idle = manager.add_task("Idle".to_string(), false).unwrap();
+
+    let day = 1_700_000_000;
+    manager.start_task_at(writing, day).unwrap();
+    manager.stop_task_at(writing, day + 30 * 60).unwrap();
+    manager.start_task_at(review, day + 3600).unwrap();
+    manager
+        .complete_task_at(review, day + 3600 + 45 * 60)
+        .unwrap();
+    manager.start_task_at(writing, day + 7200).unwrap();
+    manager.stop_task_at(writing, day + 7200 + 20 * 60).unwrap();
+    // Outside the window
+    manager.start_task_at(idle, day - 7200).unwrap();
+    manager.stop_task_at(idle, day - 3600).unwrap();
+
+    let report = manager.time_report(day, day + 86_400);
+    assert_eq!(report, vec![(writing, 50), (review, 45)]);
+
+    // Sessions are clipped to the window
+    assert_eq!(
+        manager.time_report(day + 15 * 60, day + 3600),
+        vec![(writing, 15)]
+    );
+}
 
-        // Check initial active tasks
-        let active_tasks = manager.get_active_tasks();
-        let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+#[test]
+fn test_get_task_by_text_exact_match() {
+    let manager = TaskManager::new();
+    let first = manager.add_task("Call Alex".to_string(), false).unwrap();
+    let second = manager.add_subtask(first, "Call Alex".to_string()).unwrap();
+    manager.add_task("call alex".to_string(), false).unwrap();
 
-        // Expected active tasks: Task A1, Task B1, Task C1
-        let expected_active = vec![task_a1, task_b1, task_c1];
-        let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
+    let ids: Vec<usize> = manager
+        .get_task_by_text("Call Alex")
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(ids, vec![first, second]);
+    assert!(manager.get_task_by_text("Call").is_empty());
+}
+
+#[test]
+fn test_complete_task_reports_newly_unblocked() {
+    let manager = TaskManager::new();
+    let blocker = manager.add_task("Blocker".to_string(), false).unwrap();
+    let waiting = manager.add_task("Waiting".to_string(), false).unwrap();
+    let unrelated = manager.add_task("Unrelated".to_string(), false).unwrap();
+    let project = manager.add_task("Project".to_string(), true).unwrap();
+    let step1 = manager.add_subtask(project, "Step 1".to_string()).unwrap();
+    let step2 = manager.add_subtask(project, "Step 2".to_string()).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&waiting]
+            .lock()
+            .unwrap()
+            .add_dependency(blocker, true);
+    }
+
+    assert_eq!(
+        manager.complete_task_unblocking(blocker).unwrap(),
+        vec![waiting]
+    );
+    assert_eq!(
+        manager.complete_task_unblocking(step1).unwrap(),
+        vec![step2]
+    );
+    assert!(manager
+        .complete_task_unblocking(unrelated)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_dump_structure_matches_inserted_tree() {
+    use crate::core::task_manager::TaskDump;
+
+    let manager = TaskManager::new();
+    let root = manager.add_task("Root".to_string(), false).unwrap();
+    let child = manager.add_subtask(root, "Child".to_string()).unwrap();
+    let other = manager.add_task("Other".to_string(), true).unwrap();
+    manager.complete_task(child).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&other].lock().unwrap().add_dependency(child, true);
+    }
+
+    assert_eq!(
+        manager.dump_structure(),
+        vec![
+            TaskDump {
+                id: root,
+                text: "Root".to_string(),
+                completed: false,
+                ordered: false,
+                parent: None,
+                subtasks: vec![child],
+                predecessors: vec![],
+            },
+            TaskDump {
+                id: child,
+                text: "Child".to_string(),
+                completed: true,
+                ordered: true,
+                parent: Some(root),
+                subtasks: vec![],
+                predecessors: vec![],
+            },
+            TaskDump {
+                id: other,
+                text: "Other".to_string(),
+                completed: false,
+                ordered: true,
+                parent: None,
+                subtasks: vec![],
+                predecessors: vec![Dependency {
+                    id: child,
+                    hard: true
+                }],
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_complete_task_with_predecessors_completes_chain() {
+    let manager = TaskManager::new();
+    let design = manager.add_task("Design".to_string(), false).unwrap();
+    let build = manager.add_task("Build".to_string(), false).unwrap();
+    let test = manager.add_task("Test".to_string(), false).unwrap();
+    let ship = manager.add_task("Ship".to_string(), false).unwrap();
+    let unrelated = manager.add_task("Unrelated".to_string(), false).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&build].lock().unwrap().add_dependency(design, true);
+        tasks[&test].lock().unwrap().add_dependency(build, true);
+        tasks[&ship].lock().unwrap().add_dependency(test, true);
+    }
+
+    manager.complete_task_with_predecessors(ship).unwrap();
+
+    for id in [design, build, test, ship] {
+        assert!(manager.get_task(id).unwrap().completed);
+    }
+    assert!(!manager.get_task(unrelated).unwrap().completed);
+}
 
-        assert_eq!(active_task_ids, expected_active_set);
+#[test]
+fn test_reset_subtree_reopens_everything() {
+    let manager = TaskManager::new();
+    let checklist = manager.add_task("Checklist".to_string(), false).unwrap();
+    let first = manager.add_subtask(checklist, "First".to_string()).unwrap();
+    let second = manager
+        .add_subtask(checklist, "Second".to_string())
+        .unwrap();
+    let nested = manager.add_subtask(second, "Nested".to_string()).unwrap();
+    manager.start_task(nested).unwrap();
+    for id in [first, nested, second, checklist] {
+        manager.complete_task(id).unwrap();
+    }
+    assert!(manager.get_active_tasks().is_empty());
+
+    assert_eq!(manager.reset_subtree(checklist).unwrap(), 4);
+
+    for id in [checklist, first, second, nested] {
+        let task = manager.get_task(id).unwrap();
+        assert!(!task.completed);
+        assert!(task.completed_at.is_none());
+        assert!(!task.in_progress);
+    }
+    let active: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active, vec![first, nested]);
+}
+
+#[test]
+fn test_get_active_ids_matches_active_tasks() {
+    let manager = TaskManager::new();
+    let assert_matches = |manager: &TaskManager| {
+        let mut expected: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+        expected.sort();
+        assert_eq!(manager.get_active_ids(), expected);
+    };
+    assert_matches(&manager);
 
-        // Complete Task A1
-        manager.complete_task(task_a1).unwrap();
+    let ordered = manager.add_task("Ordered".to_string(), true).unwrap();
+    let step1 = manager.add_subtask(ordered, "Step 1".to_string()).unwrap();
+    manager.add_subtask(ordered, "Step 2".to_string()).unwrap();
+    assert_matches(&manager);
 
-        // Now, Task A2 should become active
-        let active_tasks = manager.get_active_tasks();
-        let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+    let unordered = manager.add_task("Unordered".to_string(), false).unwrap();
+    let a = manager.add_subtask(unordered, "A".to_string()).unwrap();
+    manager.add_subtask(a, "A1".to_string()).unwrap();
+    manager.add_subtask(unordered, "B".to_string()).unwrap();
+    manager.add_task("Single".to_string(), false).unwrap();
+    assert_matches(&manager);
 
-        let expected_active = vec![task_a2, task_b1, task_c1];
-        let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
-        assert_eq!(active_task_ids, expected_active_set);
+    manager.complete_task(step1).unwrap();
+    manager.complete_task(a).unwrap();
+    assert_matches(&manager);
+    assert!(!manager.get_active_ids().is_empty());
 
-        // Complete Task B1
-        manager.complete_task(task_b1).unwrap();
+    // Predecessors, waiting notes, self-dependencies and completed parents with open children
+    let blocker = manager.add_task("Blocker".to_string(), false).unwrap();
+    let blocked = manager.add_task("Blocked".to_string(), false).unwrap();
+    manager.set_dependency(blocked, blocker, true).unwrap();
+    let waiting = manager.add_task("Waiting".to_string(), false).unwrap();
+    manager
+        .set_waiting(waiting, Some("Bob".to_string()))
+        .unwrap();
+    let closed = manager.add_task("Closed".to_string(), false).unwrap();
+    manager
+        .add_subtask(closed, "Still open".to_string())
+        .unwrap();
+    manager.tasks.lock().unwrap()[&closed]
+        .lock()
+        .unwrap()
+        .completed = true;
+    let looped = manager.add_task("Looped".to_string(), false).unwrap();
+    manager.tasks.lock().unwrap()[&looped]
+        .lock()
+        .unwrap()
+        .add_dependency(looped, true);
+    assert_matches(&manager);
 
-        // No change in active tasks yet since B2 depends on C
-        let active_tasks = manager.get_active_tasks();
-        let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+    manager.complete_task(blocker).unwrap();
+    assert_matches(&manager);
+    assert!(manager.get_active_ids().contains(&blocked));
+}
 
-        assert_eq!(active_task_ids, expected_active_set);
+#[test]
+fn test_comments_keep_insertion_order() {
+    let manager = TaskManager::new();
+    let id = manager.add_task("Migration".to_string(), false).unwrap();
 
-        // Complete Task C1
-        manager.complete_task(task_c1).unwrap();
+    manager
+        .add_comment(id, "Started on schema".to_string(), 2_000)
+        .unwrap();
+    manager
+        .add_comment(id, "Backfill done".to_string(), 1_000)
+        .unwrap();
+    assert!(manager.add_comment(id, "  ".to_string(), 3_000).is_err());
 
-        // Task C2 becomes active
-        let active_tasks = manager.get_active_tasks();
-        let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+    let comments = manager.get_comments(id).unwrap();
+    assert_eq!(comments.len(), 2);
+    assert_eq!(comments[0].text, "Started on schema");
+    assert_eq!(comments[0].timestamp, 2_000);
+    assert_eq!(comments[1].text, "Backfill done");
+    assert_eq!(comments[1].timestamp, 1_000);
+    assert!(manager.get_comments(999).is_err());
+}
 
-        let expected_active = vec![task_a2, task_b2, task_c2];
-        let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
-        assert_eq!(active_task_ids, expected_active_set);
+#[test]
+fn test_blocked_count_follows_dependencies_and_order() {
+    let manager = TaskManager::new();
+    let project = manager.add_task("Project".to_string(), true).unwrap();
+    let gate = manager.add_subtask(project, "Gate".to_string()).unwrap();
+    let next_step = manager
+        .add_subtask(project, "Next step".to_string())
+        .unwrap();
+    let nested = manager
+        .add_subtask(next_step, "Nested".to_string())
+        .unwrap();
+    let external = manager.add_task("External".to_string(), false).unwrap();
+    let done = manager.add_task("Done".to_string(), false).unwrap();
+    manager.add_task("Free".to_string(), false).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&external].lock().unwrap().add_dependency(gate, true);
+        tasks[&done].lock().unwrap().add_dependency(gate, true);
+    }
+    manager.complete_task(done).unwrap();
 
-        // Complete Task C2
-        manager.complete_task(task_c2).unwrap();
+    // Next step, its nested child and the external dependent; the finished task doesn't count
+    assert_eq!(manager.blocked_count(gate).unwrap(), 3);
+    assert_eq!(manager.blocked_count(nested).unwrap(), 0);
 
-        // Task B2's dependency on Task C is satisfied
-        let active_tasks = manager.get_active_tasks();
-        let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+    manager.complete_task(gate).unwrap();
+    assert_eq!(manager.blocked_count(gate).unwrap(), 0);
+}
 
-        let expected_active = vec![task_a2, task_b2];
-        let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
-        assert_eq!(active_task_ids, expected_active_set);
+#[test]
+fn test_load_rejects_future_schema_version() {
+    let manager = TaskManager::new();
+    let existing = manager.add_task("Keep me".to_string(), false).unwrap();
 
-        // Complete Task B2
-        manager.complete_task(task_b2).unwrap();
+    let path = temp_path("future_version_test.json");
+    std::fs::write(
+        &path,
+        r#"{"version": 999, "tasks": [], "root_tasks": [], "next_id": 1, "new_field": true}"#,
+    )
+    .unwrap();
+    let result = manager.load_from_file(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
 
-        // Task A2 remains active
-        let active_tasks = manager.get_active_tasks();
-        let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+    assert!(result.unwrap_err().starts_with("FutureVersion"));
+    assert!(manager.get_task(existing).is_some());
+}
 
-        let expected_active = vec![task_a2];
-        let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
-        assert_eq!(active_task_ids, expected_active_set);
+#[test]
+fn test_tag_subtree_tags_every_descendant() {
+    let manager = TaskManager::new();
+    let project = manager.add_task("Project".to_string(), false).unwrap();
+    let child = manager.add_subtask(project, "Child".to_string()).unwrap();
+    let grandchild = manager
+        .add_subtask(child, "Grandchild".to_string())
+        .unwrap();
+    let outside = manager.add_task("Outside".to_string(), false).unwrap();
+    manager.add_tag(child, "work".to_string()).unwrap();
 
-        // Complete Task A2
-        manager.complete_task(task_a2).unwrap();
+    assert_eq!(manager.tag_subtree(project, "work".to_string()).unwrap(), 3);
 
-        // Task A3 depends on B2 (which is completed), so it becomes active
-        let active_tasks = manager.get_active_tasks();
-        let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+    for id in [project, child, grandchild] {
+        assert_eq!(manager.get_task(id).unwrap().tags, vec!["work"]);
+    }
+    assert!(manager.get_task(outside).unwrap().tags.is_empty());
+}
 
-        let expected_active = vec![task_a3];
-        let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
-        assert_eq!(active_task_ids, expected_active_set);
+#[test]
+fn test_newly_overdue_reports_each_task_once() {
+    let manager = TaskManager::new();
+    let report = manager.add_task("Report".to_string(), false).unwrap();
+    let later = manager.add_task("Later".to_string(), false).unwrap();
+    let finished = manager.add_task("Finished".to_string(), false).unwrap();
+    manager.set_due_date(report, Some(1_500)).unwrap();
+    manager.set_due_date(later, Some(5_000)).unwrap();
+    manager.set_due_date(finished, Some(1_200)).unwrap();
+    manager.complete_task(finished).unwrap();
 
-        // Toggle Task A to unordered
-        manager.toggle_ordered(task_a).unwrap();
+    let ids = |previous_now, now| -> Vec<usize> {
+        manager
+            .newly_overdue(previous_now, now)
+            .iter()
+            .map(|t| t.id)
+            .collect()
+    };
+    assert!(ids(0, 1_000).is_empty());
+    assert_eq!(ids(1_000, 2_000), vec![report]);
+    assert!(ids(2_000, 3_000).is_empty());
+}
 
-        // Now, Task A3 should have no internal predecessors due to order
-        // Since its explicit predecessor B2 is completed, Task A3 remains active
-        let active_tasks = manager.get_active_tasks();
-        let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
-        assert_eq!(active_task_ids, expected_active_set);
+#[test]
+fn test_archive_to_file_moves_completed_subtree_out() {
+    let manager = TaskManager::new();
+    let done = manager.add_task("Done project".to_string(), false).unwrap();
+    let step = manager.add_subtask(done, "Step".to_string()).unwrap();
+    let open = manager.add_task("Open project".to_string(), false).unwrap();
+    manager.add_subtask(open, "Open step".to_string()).unwrap();
+    manager.complete_task(step).unwrap();
+    manager.complete_task(done).unwrap();
+    manager.set_dependency(step, open, false).unwrap();
 
-        // Reorder subtasks of Task A
+    let path = temp_path("archive_test.json");
+    assert!(manager
+        .archive_to_file(open, path.to_str().unwrap())
+        .is_err());
+    assert_eq!(
         manager
-            .reorder_subtasks(task_a, vec![task_a3, task_a1, task_a2])
-            .unwrap();
+            .archive_to_file(done, path.to_str().unwrap())
+            .unwrap(),
+        2
+    );
+
+    assert!(manager.get_task(done).is_none());
+    assert!(manager.get_task(step).is_none());
+    assert_eq!(*manager.root_tasks.lock().unwrap(), vec![open]);
+
+    let archive = TaskManager::new();
+    archive.load_from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(archive.get_task(step).unwrap().parent, Some(done));
+    assert!(archive.get_task(step).unwrap().predecessors.is_empty());
+    assert!(archive.load_repairs().is_empty());
+}
+
+#[test]
+fn test_get_recent_tasks_newest_first() {
+    let manager = TaskManager::new();
+    let first = manager.add_task("First".to_string(), false).unwrap();
+    manager.add_subtask(first, "Second".to_string()).unwrap();
+    let third = manager.add_task("Third".to_string(), false).unwrap();
+    let fourth = manager.add_subtask(first, "Fourth".to_string()).unwrap();
+    let fifth = manager.add_task("Fifth".to_string(), false).unwrap();
+
+    let ids: Vec<usize> = manager.get_recent_tasks(3).iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![fifth, fourth, third]);
+    assert_eq!(manager.get_recent_tasks(10).len(), 5);
+}
+
+#[test]
+fn test_freeze_order_survives_toggle_to_unordered() {
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Recipe".to_string(), true).unwrap();
+    let first = manager.add_subtask(parent, "Chop".to_string()).unwrap();
+    let second = manager.add_subtask(parent, "Fry".to_string()).unwrap();
+    let third = manager.add_subtask(parent, "Serve".to_string()).unwrap();
+
+    manager.freeze_order_as_dependencies(parent).unwrap();
+    manager.toggle_ordered(parent).unwrap();
+    assert!(!manager.get_task(parent).unwrap().ordered);
+    assert_eq!(
+        manager.get_task(third).unwrap().predecessors,
+        vec![Dependency {
+            id: second,
+            hard: true
+        }]
+    );
+
+    let active_ids = |manager: &TaskManager| -> Vec<usize> {
+        manager.get_active_tasks().iter().map(|t| t.id).collect()
+    };
+    assert_eq!(active_ids(&manager), vec![first]);
+    manager.complete_task(first).unwrap();
+    assert_eq!(active_ids(&manager), vec![second]);
+    manager.complete_task(second).unwrap();
+    assert_eq!(active_ids(&manager), vec![third]);
+
+    assert!(manager.freeze_order_as_dependencies(parent).is_err());
+}
+
+#[test]
+fn test_has_unsaved_changes_tracks_dirty_flag() {
+    let path = temp_path("unsaved_changes_test.json");
+    let source = TaskManager::new();
+    source.add_task("Saved".to_string(), false).unwrap();
+    source.save_to_file(path.to_str().unwrap()).unwrap();
+
+    let manager = TaskManager::new();
+    manager.load_from_file(path.to_str().unwrap()).unwrap();
+    assert!(!manager.has_unsaved_changes());
+
+    manager.add_task("Unsaved".to_string(), false).unwrap();
+    assert!(manager.has_unsaved_changes());
+
+    manager.save_to_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(!manager.has_unsaved_changes());
+}
+
+#[test]
+fn test_get_all_tags_sorted_and_deduped() {
+    let manager = TaskManager::new();
+    let a = manager.add_task("A".to_string(), false).unwrap();
+    let b = manager.add_subtask(a, "B".to_string()).unwrap();
+    manager.add_task("Untagged".to_string(), false).unwrap();
+    manager.add_tag(a, "work".to_string()).unwrap();
+    manager.add_tag(a, "errand".to_string()).unwrap();
+    manager.add_tag(b, "work".to_string()).unwrap();
+    manager.add_tag(b, "calls".to_string()).unwrap();
+
+    assert_eq!(manager.get_all_tags(), vec!["calls", "errand", "work"]);
+}
+
+#[test]
+fn test_move_after_reparents_next_to_anchor() {
+    let manager = TaskManager::new();
+    let project = manager.add_task("Project".to_string(), true).unwrap();
+    let first = manager.add_subtask(project, "First".to_string()).unwrap();
+    let second = manager.add_subtask(project, "Second".to_string()).unwrap();
+    let loose = manager.add_task("Loose".to_string(), false).unwrap();
+    let loose_child = manager
+        .add_subtask(loose, "Loose child".to_string())
+        .unwrap();
+
+    manager.move_after(loose, first).unwrap();
+    assert_eq!(
+        manager.get_task(project).unwrap().subtasks,
+        vec![first, loose, second]
+    );
+    assert_eq!(manager.get_task(loose).unwrap().parent, Some(project));
+    assert_eq!(*manager.root_tasks.lock().unwrap(), vec![project]);
+
+    manager.move_before(second, first).unwrap();
+    assert_eq!(
+        manager.get_task(project).unwrap().subtasks,
+        vec![second, first, loose]
+    );
+
+    assert!(manager.move_after(loose, loose_child).is_err());
+    assert!(manager.move_after(project, first).is_err());
+
+    // The task, its old parent and its new parent must all be unlocked
+    let other = manager.add_task("Other".to_string(), false).unwrap();
+    let other_child = manager
+        .add_subtask(other, "Other child".to_string())
+        .unwrap();
+    for locked in [other_child, other, project] {
+        manager.lock_task(locked).unwrap();
+        assert!(manager.move_after(other_child, first).is_err());
+        manager.unlock_task(locked).unwrap();
+    }
+    assert_eq!(manager.get_task(other_child).unwrap().parent, Some(other));
+    manager.move_after(other_child, first).unwrap();
+    assert_eq!(manager.get_task(other_child).unwrap().parent, Some(project));
+}
+
+#[test]
+fn test_ready_queue_puts_unblockers_first() {
+    let manager = TaskManager::new();
+    let unrelated = manager.add_task("Unrelated".to_string(), false).unwrap();
+    let urgent = manager.add_task("Urgent".to_string(), false).unwrap();
+    let x = manager.add_task("X".to_string(), false).unwrap();
+    let y = manager.add_task("Y".to_string(), false).unwrap();
+    manager.set_priority(urgent, 5).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&y].lock().unwrap().add_dependency(x, true);
+    }
+
+    let ids: Vec<usize> = manager.ready_queue().iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![x, urgent, unrelated]);
+}
+
+#[test]
+fn test_completed_parent_keeps_incomplete_children_active() {
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Parent".to_string(), false).unwrap();
+    let done = manager.add_subtask(parent, "Done".to_string()).unwrap();
+    let open = manager
+        .add_subtask(parent, "Still open".to_string())
+        .unwrap();
+    manager.complete_task(done).unwrap();
+    manager.complete_task(parent).unwrap();
+
+    let active: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active, vec![open]);
 
-        // Complete Task A3
-        manager.complete_task(task_a3).unwrap();
+    manager.complete_task(open).unwrap();
+    assert!(manager.get_active_tasks().is_empty());
+}
+
+#[test]
+fn test_duplicate_task_shallow_copies_only_the_task() {
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Parent".to_string(), true).unwrap();
+    let original = manager
+        .add_subtask(parent, "Weekly review".to_string())
+        .unwrap();
+    let after = manager.add_subtask(parent, "After".to_string()).unwrap();
+    manager
+        .add_subtask(original, "Inbox zero".to_string())
+        .unwrap();
+    manager.add_tag(original, "routine".to_string()).unwrap();
+    manager.set_priority(original, 3).unwrap();
+    manager.complete_task(original).unwrap();
+
+    let copy_id = manager.duplicate_task_shallow(original).unwrap();
+    let copy = manager.get_task(copy_id).unwrap();
+
+    assert!(copy.subtasks.is_empty());
+    assert!(!copy.completed);
+    assert_eq!(copy.text, "Weekly review");
+    assert_eq!(copy.tags, vec!["routine"]);
+    assert_eq!(copy.priority, 3);
+    assert_eq!(copy.parent, Some(parent));
+    assert_eq!(
+        manager.get_task(parent).unwrap().subtasks,
+        vec![original, copy_id, after]
+    );
+}
+
+#[test]
+fn test_sort_roots_alphabetically() {
+    use crate::core::task_manager::RootSort;
+
+    let manager = TaskManager::new();
+    let gamma = manager.add_task("gamma".to_string(), false).unwrap();
+    let alpha = manager.add_task("Alpha".to_string(), false).unwrap();
+    let beta = manager.add_task("beta".to_string(), false).unwrap();
+
+    manager.sort_roots(RootSort::Manual);
+    let texts = |manager: &TaskManager| -> Vec<String> {
+        manager
+            .get_root_tasks()
+            .into_iter()
+            .map(|t| t.text)
+            .collect()
+    };
+    assert_eq!(texts(&manager), vec!["gamma", "Alpha", "beta"]);
+
+    manager.sort_roots(RootSort::Alphabetical);
+    assert_eq!(texts(&manager), vec!["Alpha", "beta", "gamma"]);
+    assert_eq!(
+        *manager.root_tasks.lock().unwrap(),
+        vec![alpha, beta, gamma]
+    );
+}
+
+#[test]
+fn test_find_and_merge_duplicate_siblings() {
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Imported".to_string(), false).unwrap();
+    let first = manager.add_subtask(parent, "Buy milk".to_string()).unwrap();
+    let second = manager
+        .add_subtask(parent, "  buy MILK ".to_string())
+        .unwrap();
+    let nested = manager
+        .add_subtask(second, "Check fridge".to_string())
+        .unwrap();
+    // Same text under a different parent is not a duplicate
+    manager.add_task("Buy milk".to_string(), false).unwrap();
+
+    assert_eq!(manager.find_duplicates(), vec![vec![first, second]]);
+
+    manager.merge_tasks(first, second).unwrap();
+    assert!(manager.get_task(second).is_none());
+    assert_eq!(manager.get_task(first).unwrap().subtasks, vec![nested]);
+    assert_eq!(manager.get_task(nested).unwrap().parent, Some(first));
+    assert_eq!(manager.get_task(parent).unwrap().subtasks, vec![first]);
+    assert!(manager.find_duplicates().is_empty());
+}
+
+#[test]
+fn test_export_ics_vtodo_with_due_date() {
+    use crate::core::task_manager::parse_date;
+
+    let manager = TaskManager::new();
+    let id = manager
+        .add_task("Pay rent, on time".to_string(), false)
+        .unwrap();
+    manager.set_due_date(id, parse_date("2024-03-01")).unwrap();
+    manager.set_priority(id, 9).unwrap();
+
+    let ics = manager.export_ics();
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(ics.ends_with("END:VCALENDAR\r\n"));
+
+    let lines: Vec<&str> = ics.split("\r\n").collect();
+    let begin = lines.iter().position(|l| *l == "BEGIN:VTODO").unwrap();
+    let end = lines.iter().position(|l| *l == "END:VTODO").unwrap();
+    let vtodo = &lines[begin..end];
+    assert!(vtodo.contains(&"SUMMARY:Pay rent\\, on time"));
+    assert!(vtodo.contains(&"DUE;VALUE=DATE:20240301"));
+    assert!(vtodo.contains(&"PRIORITY:1"));
+    assert!(vtodo.iter().any(|l| l.starts_with("DTSTAMP:")));
+}
+
+#[test]
+fn test_export_ics_folds_long_lines_and_escapes_line_breaks() {
+    let manager = TaskManager::new();
+    let long_text = "Überprüfung der Straßenbeleuchtung ".repeat(5);
+    manager.add_task(long_text.clone(), false).unwrap();
+    manager
+        .add_task("First line\r\nsecond line\rthird".to_string(), false)
+        .unwrap();
+
+    let ics = manager.export_ics();
+    // Every CR belongs to a CRLF line ending
+    assert_eq!(ics.matches('\r').count(), ics.matches("\r\n").count());
+    for line in ics.split("\r\n") {
+        assert!(line.len() <= 75, "line too long: {:?}", line);
+    }
+
+    let unfolded = ics.replace("\r\n ", "");
+    let summaries: Vec<&str> = unfolded
+        .split("\r\n")
+        .filter_map(|l| l.strip_prefix("SUMMARY:"))
+        .collect();
+    assert!(summaries.contains(&long_text.as_str()));
+    assert!(summaries.contains(&"First line\\nsecond line\\nthird"));
+}
+
+#[test]
+fn test_all_complete() {
+    let manager = TaskManager::new();
+    assert!(manager.all_complete());
+
+    let root = manager.add_task("Root".to_string(), false).unwrap();
+    let child = manager.add_subtask(root, "Child".to_string()).unwrap();
+    assert!(!manager.all_complete());
+
+    manager.complete_task(child).unwrap();
+    assert!(!manager.all_complete());
+    manager.complete_task(root).unwrap();
+    assert!(manager.all_complete());
+}
+
+#[test]
+fn test_sort_key_orders_manual_subtasks() {
+    use crate::core::task_manager::SubtaskSort;
+
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Parent".to_string(), false).unwrap();
+    let a = manager.add_subtask(parent, "A".to_string()).unwrap();
+    let b = manager.add_subtask(parent, "B".to_string()).unwrap();
+    let c = manager.add_subtask(parent, "C".to_string()).unwrap();
+
+    manager.set_sort_key(a, Some(1.0)).unwrap();
+    manager.set_sort_key(b, Some(2.0)).unwrap();
+    manager.set_sort_key(c, Some(1.5)).unwrap();
 
-        // Since Task A is unordered, other subtasks remain incomplete but are not active
-        // Because Task A1 and Task A2 were already completed
-        let active_tasks = manager.get_active_tasks();
-        assert!(active_tasks.is_empty());
+    let order: Vec<usize> = manager
+        .get_subtasks_sorted(parent, SubtaskSort::Manual)
+        .unwrap()
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(order, vec![a, c, b]);
+    // Positional order is untouched
+    assert_eq!(manager.get_task(parent).unwrap().subtasks, vec![a, b, c]);
+    assert!(manager.set_sort_key(a, Some(f64::NAN)).is_err());
 
-        // Uncomplete Task A1
-        manager.uncomplete_task(task_a1).unwrap();
+    // Other sorts break ties by the manual order
+    manager.complete_task(a).unwrap();
+    manager.set_priority(b, 2).unwrap();
+    let ids = |sort| -> Vec<usize> {
+        manager
+            .get_subtasks_sorted(parent, sort)
+            .unwrap()
+            .iter()
+            .map(|t| t.id)
+            .collect()
+    };
+    assert_eq!(ids(SubtaskSort::IncompleteFirst), vec![c, b, a]);
+    assert_eq!(ids(SubtaskSort::DueDate), vec![a, c, b]);
+    assert_eq!(ids(SubtaskSort::Priority), vec![b, a, c]);
+}
+
+#[test]
+fn test_next_sibling_active() {
+    let manager = TaskManager::new();
+    let checklist = manager.add_task("Checklist".to_string(), true).unwrap();
+    let step1 = manager
+        .add_subtask(checklist, "Step 1".to_string())
+        .unwrap();
+    let step2 = manager
+        .add_subtask(checklist, "Step 2".to_string())
+        .unwrap();
+    let step3 = manager
+        .add_subtask(checklist, "Step 3".to_string())
+        .unwrap();
+
+    manager.complete_task(step1).unwrap();
+    let next = manager.next_sibling_active(step1).unwrap().unwrap();
+    assert_eq!(next.id, step2);
+
+    manager.complete_task(step2).unwrap();
+    assert_eq!(
+        manager.next_sibling_active(step1).unwrap().unwrap().id,
+        step3
+    );
+    assert!(manager.next_sibling_active(step3).unwrap().is_none());
+
+    manager.toggle_ordered(checklist).unwrap();
+    assert!(manager.next_sibling_active(step1).unwrap().is_none());
+}
+
+#[test]
+fn test_set_priority_bulk_skips_missing() {
+    let manager = TaskManager::new();
+    let a = manager.add_task("A".to_string(), false).unwrap();
+    let b = manager.add_task("B".to_string(), false).unwrap();
+    let untouched = manager.add_task("C".to_string(), false).unwrap();
+
+    let updated = manager.set_priority_bulk(vec![a, 999, b, a], 4).unwrap();
+    assert_eq!(updated, 2);
+    assert_eq!(manager.get_task(a).unwrap().priority, 4);
+    assert_eq!(manager.get_task(b).unwrap().priority, 4);
+    assert_eq!(manager.get_task(untouched).unwrap().priority, 0);
+}
+
+#[test]
+fn test_active_traversal_preserves_ordered_semantics() {
+    let manager = TaskManager::new();
+    let task_a = manager.add_task("Task A".to_string(), true).unwrap();
+    let task_b = manager.add_task("Task B".to_string(), false).unwrap();
+    let task_c = manager.add_task("Task C".to_string(), true).unwrap();
+    let task_a1 = manager.add_subtask(task_a, "Task A1".to_string()).unwrap();
+    let task_a2 = manager.add_subtask(task_a, "Task A2".to_string()).unwrap();
+    let task_a3 = manager.add_subtask(task_a, "Task A3".to_string()).unwrap();
+    let task_b1 = manager.add_subtask(task_b, "Task B1".to_string()).unwrap();
+    let task_b2 = manager.add_subtask(task_b, "Task B2".to_string()).unwrap();
+    let task_c1 = manager.add_subtask(task_c, "Task C1".to_string()).unwrap();
+    let task_c2 = manager.add_subtask(task_c, "Task C2".to_string()).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&task_a3]
+            .lock()
+            .unwrap()
+            .add_dependency(task_b2, true);
+        tasks[&task_b2].lock().unwrap().add_dependency(task_c, true);
+    }
+
+    let active = |manager: &TaskManager| -> Vec<usize> {
+        manager.get_active_tasks().iter().map(|t| t.id).collect()
+    };
+
+    // Pre-order: roots in order, ordered parents expose only their first open step
+    assert_eq!(active(&manager), vec![task_a1, task_b1, task_c1]);
+
+    manager.complete_task(task_a1).unwrap();
+    assert_eq!(active(&manager), vec![task_a2, task_b1, task_c1]);
+
+    // B2 stays blocked until C is done
+    manager.complete_task(task_b1).unwrap();
+    assert_eq!(active(&manager), vec![task_a2, task_c1]);
 
-        // Now, Task A1 should be active again
-        let active_tasks = manager.get_active_tasks();
-        let active_task_ids: HashSet<usize> = active_tasks.iter().map(|t| t.id).collect();
+    manager.complete_task(task_c1).unwrap();
+    assert_eq!(active(&manager), vec![task_a2, task_c2]);
 
-        let expected_active = vec![task_a1];
-        let expected_active_set: HashSet<usize> = expected_active.into_iter().collect();
-        assert_eq!(active_task_ids, expected_active_set);
+    // A parent whose subtasks are all done becomes active itself
+    manager.complete_task(task_c2).unwrap();
+    assert_eq!(active(&manager), vec![task_a2, task_c]);
 
-        // Complete Task A1
-        manager.complete_task(task_a1).unwrap();
+    manager.complete_task(task_c).unwrap();
+    assert_eq!(active(&manager), vec![task_a2, task_b2]);
 
-        // All tasks should now be completed
-        let active_tasks = manager.get_active_tasks();
-        assert!(active_tasks.is_empty());
+    manager.complete_task(task_b2).unwrap();
+    manager.complete_task(task_a2).unwrap();
+    assert_eq!(active(&manager), vec![task_a3, task_b]);
+
+    // Unordered parents expose every open subtask
+    manager.uncomplete_task(task_a1, false).unwrap();
+    assert_eq!(active(&manager), vec![task_a1, task_b]);
+    manager.toggle_ordered(task_a).unwrap();
+    assert_eq!(active(&manager), vec![task_a1, task_a3, task_b]);
+}
+
+#[test]
+fn test_active_traversal_visits_shared_child_once() {
+    let manager = TaskManager::new();
+    let first = manager.add_task("First".to_string(), false).unwrap();
+    let second = manager.add_task("Second".to_string(), false).unwrap();
+    let shared = manager.add_subtask(first, "Shared".to_string()).unwrap();
+    let own = manager.add_subtask(second, "Own".to_string()).unwrap();
+    manager.tasks.lock().unwrap()[&second]
+        .lock()
+        .unwrap()
+        .subtasks
+        .insert(0, shared);
+
+    let active: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active, vec![shared, own]);
+
+    // A deep chain is walked without recursion
+    let mut parent = manager.add_task("Deep".to_string(), false).unwrap();
+    for i in 0..5_000 {
+        parent = manager.add_subtask(parent, format!("Level {}", i)).unwrap();
     }
+    let active: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active, vec![shared, own, parent]);
+}
+
+#[test]
+fn test_remaining_tasks_in_project() {
+    let manager = TaskManager::new();
+    let project = manager.add_task("Project".to_string(), true).unwrap();
+    let design = manager.add_subtask(project, "Design".to_string()).unwrap();
+    let build = manager.add_subtask(project, "Build".to_string()).unwrap();
+    let frontend = manager.add_subtask(build, "Frontend".to_string()).unwrap();
+    let backend = manager.add_subtask(build, "Backend".to_string()).unwrap();
+    let ship = manager.add_subtask(project, "Ship".to_string()).unwrap();
+    manager
+        .add_task("Other project".to_string(), false)
+        .unwrap();
+
+    manager.complete_task(design).unwrap();
+    manager.complete_task(frontend).unwrap();
 
-    #[test]
-    fn test_get_parent_tasks() {
-        let manager = TaskManager::new();
-        let parent_id = manager.add_task("Parent Task".to_string(), true);
-        let subtask_id = manager
-            .add_subtask(parent_id, "Subtask".to_string())
+    let remaining: Vec<usize> = manager
+        .remaining_tasks(project)
+        .unwrap()
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(remaining, vec![project, build, backend, ship]);
+    assert!(manager.remaining_tasks(999).is_err());
+}
+
+#[test]
+fn test_compressed_save_round_trip() {
+    let manager = TaskManager::new();
+    let root = manager.add_task("Archive me".to_string(), true).unwrap();
+    let child = manager.add_subtask(root, "Packed".to_string()).unwrap();
+    manager.complete_task(child).unwrap();
+
+    let path = temp_path("compressed_test.json.gz");
+    manager.save_to_file(path.to_str().unwrap()).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(&bytes[..2], &[0x1f, 0x8b]);
+
+    let reloaded = TaskManager::new();
+    reloaded.load_from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        reloaded.export_state_json().unwrap(),
+        manager.export_state_json().unwrap()
+    );
+    assert!(reloaded.get_task(child).unwrap().completed);
+}
+
+#[test]
+fn test_compressed_save_is_smaller() {
+    let manager = TaskManager::new();
+    for i in 0..500 {
+        let root = manager.add_task(format!("Project {}", i), false).unwrap();
+        manager
+            .add_comment(
+                root,
+                "Still waiting on the vendor".to_string(),
+                1_700_000_000,
+            )
             .unwrap();
-        let hierarchy = manager.get_parent_tasks(subtask_id).unwrap();
-        assert_eq!(hierarchy.len(), 2);
-        assert_eq!(hierarchy[0].text, "Subtask");
-        assert_eq!(hierarchy[1].text, "Parent Task");
     }
+
+    let plain = temp_path("size_test.json");
+    let compressed = temp_path("size_test.json.gz");
+    manager.save_to_file(plain.to_str().unwrap()).unwrap();
+    manager.save_to_file(compressed.to_str().unwrap()).unwrap();
+
+    let plain_size = std::fs::metadata(&plain).unwrap().len();
+    let compressed_size = std::fs::metadata(&compressed).unwrap().len();
+    std::fs::remove_file(&plain).unwrap();
+    std::fs::remove_file(&compressed).unwrap();
+
+    assert!(compressed_size * 4 < plain_size);
+}
+
+#[test]
+fn test_get_position() {
+    let manager = TaskManager::new();
+    let first_root = manager.add_task("First".to_string(), true).unwrap();
+    let second_root = manager.add_task("Second".to_string(), false).unwrap();
+    manager
+        .add_subtask(first_root, "Step 1".to_string())
+        .unwrap();
+    let step2 = manager
+        .add_subtask(first_root, "Step 2".to_string())
+        .unwrap();
+    manager
+        .add_subtask(first_root, "Step 3".to_string())
+        .unwrap();
+
+    assert_eq!(manager.get_position(step2).unwrap(), (1, 3));
+    assert_eq!(manager.get_position(first_root).unwrap(), (0, 2));
+    assert_eq!(manager.get_position(second_root).unwrap(), (1, 2));
+    assert!(manager.get_position(999).is_err());
+}
+
+#[test]
+fn test_soft_predecessor_does_not_block() {
+    let manager = TaskManager::new();
+    let hint = manager
+        .add_task("Read the docs".to_string(), false)
+        .unwrap();
+    let task = manager
+        .add_task("Write the plugin".to_string(), false)
+        .unwrap();
+
+    manager.set_dependency(task, hint, false).unwrap();
+    let active: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active, vec![hint, task]);
+    let dependencies: Vec<usize> = manager
+        .get_dependencies(task)
+        .unwrap()
+        .iter()
+        .map(|t| t.id)
+        .collect();
+    assert_eq!(dependencies, vec![hint]);
+
+    // Switching it to hard makes it block
+    manager.set_dependency(task, hint, true).unwrap();
+    let active: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active, vec![hint]);
+    let stored = manager.get_task(task).unwrap();
+    assert_eq!(
+        stored.predecessors,
+        vec![Dependency {
+            id: hint,
+            hard: true
+        }]
+    );
+}
+
+#[test]
+fn test_load_migrates_bare_predecessor_ids_to_hard_dependencies() {
+    let old_format = r#"{
+        "version": 1,
+        "next_id": 4,
+        "tasks": [
+            {"id": 1, "text": "Design", "completed": false, "ordered": false,
+             "subtasks": [], "parent": null},
+            {"id": 2, "text": "Notes", "completed": false, "ordered": false,
+             "subtasks": [], "parent": null},
+            {"id": 3, "text": "Build", "completed": false, "ordered": false,
+             "subtasks": [], "parent": null, "predecessors": [1],
+             "soft_predecessors": [2]}
+        ],
+        "root_tasks": [1, 2, 3],
+        "templates": {}
+    }"#;
+    let path = temp_path("old_dependencies_test.json");
+    std::fs::write(&path, old_format).unwrap();
+
+    let manager = TaskManager::new();
+    manager.load_from_file(path.to_str().unwrap()).unwrap();
+    assert!(manager.load_repairs().is_empty());
+    let expected = vec![
+        Dependency { id: 1, hard: true },
+        Dependency { id: 2, hard: false },
+    ];
+    assert_eq!(manager.get_task(3).unwrap().predecessors, expected);
+    let active: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active, vec![1, 2]);
+
+    // Saved back in the new shape, which loads the same
+    manager.save_to_file(path.to_str().unwrap()).unwrap();
+    let reloaded = TaskManager::new();
+    reloaded.load_from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(reloaded.get_task(3).unwrap().predecessors, expected);
+}
+
+#[test]
+fn test_stats_by_tag_counts_shared_tasks_for_each_tag() {
+    let manager = TaskManager::new();
+    let shared = manager.add_task("Shared".to_string(), false).unwrap();
+    let work_only = manager.add_task("Work only".to_string(), false).unwrap();
+    let home_only = manager.add_task("Home only".to_string(), false).unwrap();
+    manager.add_tag(shared, "work".to_string()).unwrap();
+    manager.add_tag(shared, "home".to_string()).unwrap();
+    manager.add_tag(work_only, "work".to_string()).unwrap();
+    manager.add_tag(home_only, "home".to_string()).unwrap();
+    manager.complete_task(work_only).unwrap();
+
+    let stats = manager.stats_by_tag();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats["work"].total, 2);
+    assert_eq!(stats["work"].completed, 1);
+    assert_eq!(stats["work"].active, 1);
+    assert_eq!(stats["home"].total, 2);
+    assert_eq!(stats["home"].completed, 0);
+    assert_eq!(stats["home"].active, 2);
+}
+
+#[test]
+fn test_move_subtasks_as_block() {
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Parent".to_string(), true).unwrap();
+    let a = manager.add_subtask(parent, "A".to_string()).unwrap();
+    let b = manager.add_subtask(parent, "B".to_string()).unwrap();
+    let c = manager.add_subtask(parent, "C".to_string()).unwrap();
+    let d = manager.add_subtask(parent, "D".to_string()).unwrap();
+
+    // Selection order doesn't matter, the block keeps list order
+    manager.move_subtasks(Some(parent), vec![d, b], 0).unwrap();
+    assert_eq!(manager.get_task(parent).unwrap().subtasks, vec![b, d, a, c]);
+
+    // The ordered parent now starts with B
+    let active: Vec<usize> = manager.get_active_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(active, vec![b]);
+
+    let other = manager.add_task("Other".to_string(), false).unwrap();
+    assert!(manager
+        .move_subtasks(Some(parent), vec![a, other], 0)
+        .is_err());
+    assert_eq!(manager.get_task(parent).unwrap().subtasks, vec![b, d, a, c]);
+}
+
+#[test]
+fn test_should_autosave_decision() {
+    use crate::core::task_manager::should_autosave;
+    use std::time::Duration;
+
+    let interval = Duration::from_secs(300);
+    let max_defer = Duration::from_secs(60);
+    let secs = Duration::from_secs;
+
+    // Nothing to write, even when the interval is up
+    assert!(!should_autosave(None, secs(300), interval, max_defer));
+    // A fresh change waits for the interval
+    assert!(!should_autosave(
+        Some(secs(10)),
+        secs(100),
+        interval,
+        max_defer
+    ));
+    assert!(should_autosave(
+        Some(secs(10)),
+        secs(300),
+        interval,
+        max_defer
+    ));
+    // A change pending past max_defer is saved between intervals
+    assert!(should_autosave(
+        Some(secs(60)),
+        secs(100),
+        interval,
+        max_defer
+    ));
+
+    let manager = TaskManager::new();
+    let path = temp_path("save_if_dirty_test.json");
+    let path = path.to_str().unwrap();
+    assert!(manager.unsaved_for().is_none());
+    assert!(!manager.save_if_dirty(path).unwrap());
+
+    manager.add_task("Change".to_string(), false).unwrap();
+    assert!(manager.unsaved_for().is_some());
+    assert!(manager.save_if_dirty(path).unwrap());
+    assert!(manager.unsaved_for().is_none());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_subtree_completion_timeline() {
+    let manager = TaskManager::new();
+    let project = manager.add_task("Project".to_string(), false).unwrap();
+    let a = manager.add_subtask(project, "A".to_string()).unwrap();
+    let b = manager.add_subtask(project, "B".to_string()).unwrap();
+    let c = manager.add_subtask(b, "C".to_string()).unwrap();
+    let d = manager.add_subtask(project, "D".to_string()).unwrap();
+    manager
+        .add_subtask(project, "Still open".to_string())
+        .unwrap();
+    let outside = manager.add_task("Outside".to_string(), false).unwrap();
+
+    manager.complete_task_at(c, 300).unwrap();
+    manager.complete_task_at(a, 100).unwrap();
+    manager.complete_task_at(b, 300).unwrap();
+    manager.complete_task_at(d, 500).unwrap();
+    manager.complete_task_at(outside, 200).unwrap();
+
+    let timeline = manager.subtree_completion_timeline(project);
+    assert_eq!(timeline, vec![(100, 1), (300, 3), (500, 4)]);
+    assert!(timeline
+        .windows(2)
+        .all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+    assert!(manager.subtree_completion_timeline(999).is_empty());
+}
+
+#[test]
+fn test_clone_project_as_fresh_instance() {
+    use crate::core::task_manager::Recurrence;
+
+    let manager = TaskManager::new();
+    let sprint = manager.add_task("Sprint 1".to_string(), true).unwrap();
+    let plan = manager.add_subtask(sprint, "Plan".to_string()).unwrap();
+    let review = manager.add_subtask(sprint, "Review".to_string()).unwrap();
+    let notes = manager.add_subtask(review, "Notes".to_string()).unwrap();
+    let external = manager.add_task("External".to_string(), false).unwrap();
+    manager.add_tag(review, "meeting".to_string()).unwrap();
+    manager.set_priority(review, 3).unwrap();
+    manager.set_due_date(review, Some(86_400)).unwrap();
+    manager
+        .add_attachment(review, "agenda.md".to_string())
+        .unwrap();
+    manager
+        .set_recurrence(review, Some(Recurrence::Weekly))
+        .unwrap();
+    manager.set_dependency(review, plan, true).unwrap();
+    manager.set_dependency(review, external, false).unwrap();
+    manager.complete_task(plan).unwrap();
+    manager.start_task_at(review, 100).unwrap();
+    manager.complete_task(notes).unwrap();
+
+    let clone = manager
+        .clone_project(sprint, "Sprint 2".to_string())
+        .unwrap();
+    assert_eq!(
+        *manager.root_tasks.lock().unwrap(),
+        vec![sprint, external, clone]
+    );
+
+    let cloned_root = manager.get_task(clone).unwrap();
+    assert_eq!(cloned_root.text, "Sprint 2");
+    assert!(cloned_root.ordered);
+
+    let original_ids = [sprint, plan, review, notes];
+    let subtasks = manager.get_subtasks(clone).unwrap();
+    let texts: Vec<&str> = subtasks.iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(texts, vec!["Plan", "Review"]);
+    let nested = manager.get_subtasks(subtasks[1].id).unwrap();
+    assert_eq!(nested[0].text, "Notes");
+    for task in subtasks.iter().chain(&nested) {
+        assert!(!task.completed);
+        assert!(task.completed_at.is_none());
+        assert!(!original_ids.contains(&task.id));
+    }
+    assert!(manager.get_task(plan).unwrap().completed);
+
+    // Fields carry over, the timer does not, and the dependency on Plan follows the copy
+    let cloned_review = &subtasks[1];
+    assert_eq!(cloned_review.tags, vec!["meeting"]);
+    assert_eq!(cloned_review.priority, 3);
+    assert_eq!(cloned_review.due_date, Some(86_400));
+    assert_eq!(cloned_review.attachments, vec!["agenda.md"]);
+    assert_eq!(cloned_review.recurrence, Some(Recurrence::Weekly));
+    assert!(!cloned_review.in_progress);
+    assert!(cloned_review.started_at.is_none());
+    assert!(cloned_review.work_sessions.is_empty());
+    assert_eq!(
+        cloned_review.predecessors,
+        vec![
+            Dependency {
+                id: subtasks[0].id,
+                hard: true
+            },
+            Dependency {
+                id: external,
+                hard: false
+            }
+        ]
+    );
+    assert_eq!(nested[0].parent, Some(cloned_review.id));
+    assert!(manager.get_task(review).unwrap().in_progress);
+}
+
+#[test]
+fn test_tasks_completed_and_created_in_range() {
+    let manager = TaskManager::new();
+    let a = manager.add_task("A".to_string(), false).unwrap();
+    let b = manager.add_task("B".to_string(), false).unwrap();
+    let c = manager.add_task("C".to_string(), false).unwrap();
+    let legacy = manager.add_task("Legacy".to_string(), false).unwrap();
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks[&a].lock().unwrap().created_at = Some(100);
+        tasks[&b].lock().unwrap().created_at = Some(200);
+        tasks[&c].lock().unwrap().created_at = Some(300);
+        tasks[&legacy].lock().unwrap().created_at = None;
+    }
+    manager.complete_task_at(c, 350).unwrap();
+    manager.complete_task_at(a, 150).unwrap();
+    manager.complete_task_at(legacy, 400).unwrap();
+
+    let ids = |tasks: Vec<crate::core::task_manager::Task>| -> Vec<usize> {
+        tasks.iter().map(|t| t.id).collect()
+    };
+    assert_eq!(ids(manager.tasks_created_in_range(150, 300)), vec![b]);
+    assert_eq!(ids(manager.tasks_created_in_range(0, 1000)), vec![a, b, c]);
+    assert_eq!(ids(manager.tasks_completed_in_range(100, 400)), vec![a, c]);
+    assert_eq!(
+        ids(manager.tasks_completed_in_range(300, 401)),
+        vec![c, legacy]
+    );
+
+    // Reopened tasks drop out of the completed range
+    manager.uncomplete_task(a, false).unwrap();
+    assert_eq!(ids(manager.tasks_completed_in_range(100, 400)), vec![c]);
+}
+
+#[test]
+fn test_completions_between() {
+    let manager = TaskManager::new();
+    let a = manager.add_task("A".to_string(), false).unwrap();
+    let b = manager.add_task("B".to_string(), false).unwrap();
+    let c = manager.add_task("C".to_string(), false).unwrap();
+    let d = manager.add_task("D".to_string(), false).unwrap();
+    manager.add_task("Open".to_string(), false).unwrap();
+    manager.complete_task_at(a, 100).unwrap();
+    manager.complete_task_at(c, 250).unwrap();
+    manager.complete_task_at(b, 200).unwrap();
+    manager.complete_task_at(d, 300).unwrap();
+
+    assert_eq!(
+        manager.completions_between(150, 300),
+        vec![(b, 200), (c, 250)]
+    );
+    assert_eq!(
+        manager.completions_between(0, 1000),
+        vec![(a, 100), (b, 200), (c, 250), (d, 300)]
+    );
+    assert!(manager.completions_between(400, 500).is_empty());
+}
+
+#[test]
+fn test_collapse_subtasks_into_parent_text() {
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Morning".to_string(), true).unwrap();
+    let steps: Vec<usize> = ["Coffee", "Stretch", "Inbox"]
+        .iter()
+        .map(|text| manager.add_subtask(parent, text.to_string()).unwrap())
+        .collect();
+    for &step in &steps {
+        manager.complete_task_at(step, 100 + step as i64).unwrap();
+    }
+
+    manager.collapse_subtasks(parent).unwrap();
+    let collapsed = manager.get_task(parent).unwrap();
+    assert_eq!(collapsed.text, "Morning: Coffee; Stretch; Inbox");
+    assert!(collapsed.subtasks.is_empty());
+    assert!(collapsed.completed);
+    assert_eq!(collapsed.completed_at, Some(100 + steps[2] as i64));
+    for step in steps {
+        assert!(manager.get_task(step).is_none());
+    }
+
+    let open = manager.add_task("Evening".to_string(), false).unwrap();
+    manager.add_subtask(open, "Dishes".to_string()).unwrap();
+    manager.collapse_subtasks(open).unwrap();
+    let collapsed = manager.get_task(open).unwrap();
+    assert_eq!(collapsed.text, "Evening: Dishes");
+    assert!(!collapsed.completed);
+    assert!(manager.collapse_subtasks(open).is_err());
+}
+
+#[test]
+fn test_load_repairs_broken_file() {
+    let broken = r#"{
+        "version": 1,
+        "next_id": 2,
+        "tasks": [
+            {"id": 1, "text": "Root", "completed": false, "ordered": false,
+             "subtasks": [2, 99], "parent": null},
+            {"id": 2, "text": "Child", "completed": false, "ordered": false,
+             "subtasks": [], "parent": 7, "predecessors": [42]},
+            {"id": 3, "text": "Lost root", "completed": false, "ordered": false,
+             "subtasks": [], "parent": null}
+        ],
+        "root_tasks": [1, 1, 5],
+        "templates": {}
+    }"#;
+    let path = temp_path("broken_file_test.json");
+    std::fs::write(&path, broken).unwrap();
+
+    // A strict manager refuses the file and keeps what it had
+    let strict = TaskManager::new();
+    let kept = strict.add_task("Kept".to_string(), false).unwrap();
+    strict.set_repair_on_load(false);
+    assert!(strict.load_from_file(path.to_str().unwrap()).is_err());
+    assert_eq!(strict.tasks.lock().unwrap().len(), 1);
+    assert_eq!(strict.get_task(kept).unwrap().text, "Kept");
+    assert_eq!(*strict.root_tasks.lock().unwrap(), vec![kept]);
+    assert!(strict.check_integrity().is_empty());
+
+    let manager = TaskManager::new();
+    manager.load_from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(!manager.load_repairs().is_empty());
+    assert!(manager.check_integrity().is_empty());
+    assert!(manager.has_unsaved_changes());
+    assert_eq!(*manager.root_tasks.lock().unwrap(), vec![1, 3]);
+    assert_eq!(manager.get_task(1).unwrap().subtasks, vec![2]);
+    let child = manager.get_task(2).unwrap();
+    assert_eq!(child.parent, Some(1));
+    assert!(child.predecessors.is_empty());
+    assert_eq!(manager.add_task("New".to_string(), false).unwrap(), 4);
+}
+
+#[test]
+fn test_get_task_context_for_middle_sibling() {
+    let manager = TaskManager::new();
+    let parent = manager.add_task("Checklist".to_string(), true).unwrap();
+    let first = manager.add_subtask(parent, "First".to_string()).unwrap();
+    let middle = manager.add_subtask(parent, "Middle".to_string()).unwrap();
+    let last = manager.add_subtask(parent, "Last".to_string()).unwrap();
+
+    let context = manager.get_task_context(middle).unwrap();
+    assert_eq!(context.task.id, middle);
+    assert_eq!(context.previous.unwrap().id, first);
+    assert_eq!(context.next.unwrap().id, last);
+    assert_eq!(context.parent.unwrap().id, parent);
+
+    // Completed siblings are swiped past
+    manager.complete_task(first).unwrap();
+    let context = manager.get_task_context(middle).unwrap();
+    assert!(context.previous.is_none());
+
+    let root_context = manager.get_task_context(parent).unwrap();
+    assert!(root_context.parent.is_none());
+    assert!(root_context.previous.is_none() && root_context.next.is_none());
 }